@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+use crate::keymap::config_file;
+
+/// Words with a meaning of their own in the input, see `classify_input`
+const RESERVED: &[&str] = &[
+    "GET",
+    "POST",
+    "PUT",
+    "DELETE",
+    "NON",
+    "RAW",
+    "OBSERVE",
+    "UNOBSERVE",
+    "RERUN",
+    "SCAN",
+    "PING",
+];
+
+/// Short names for long commands, loaded from a TOML file like:
+///
+/// ```toml
+/// temp = "/sensors/temperature/0"
+/// up = "PUT /config/enabled true"
+/// ```
+///
+/// Words after an alias are appended to its expansion.
+pub struct Aliases {
+    aliases: Vec<(String, String)>,
+}
+
+impl Aliases {
+    /// Loads the aliases from `path`, or from the default location if it exists.
+    /// Returns the aliases and a list of problems found in the file.
+    pub fn load(path: Option<&Path>) -> (Self, Vec<String>) {
+        let mut aliases = vec![];
+        let mut warnings = vec![];
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| config_file("aliases.toml").filter(|path| path.exists()));
+        let Some(path) = path else {
+            return (Self { aliases }, warnings);
+        };
+        let table = match fs::read_to_string(&path).map(|content| content.parse::<toml::Table>()) {
+            Ok(Ok(table)) => table,
+            Ok(Err(err)) => {
+                warnings.push(format!("{}: {}", path.display(), err.message()));
+                return (Self { aliases }, warnings);
+            }
+            Err(err) => {
+                warnings.push(format!("{}: {err}", path.display()));
+                return (Self { aliases }, warnings);
+            }
+        };
+        for (name, value) in table {
+            let Some(command) = value.as_str() else {
+                warnings.push(format!("Expected a command for alias '{name}'"));
+                continue;
+            };
+            if name.contains(char::is_whitespace)
+                || RESERVED.iter().any(|word| word.eq_ignore_ascii_case(&name))
+            {
+                warnings.push(format!("'{name}' can not be used as an alias"));
+                continue;
+            }
+            aliases.push((name, command.to_string()));
+        }
+        (Self { aliases }, warnings)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// What the alias `name` stands for
+    pub fn command(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// Replaces a leading alias in `input` by its command
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let (name, rest) = input
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .unwrap_or((input.trim(), ""));
+        let command = self.command(name)?;
+        let rest = rest.trim();
+        if rest.is_empty() {
+            Some(command.to_string())
+        } else {
+            Some(format!("{command} {rest}"))
+        }
+    }
+}
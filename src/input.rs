@@ -0,0 +1,381 @@
+use std::ops::Range;
+
+use cbor_edn::StandaloneItem;
+use coap_lite::ContentFormat;
+use coap_lite::RequestType as Method;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Requests sent by `PING` without a count
+const DEFAULT_PING_COUNT: usize = 10;
+
+/// What a line committed in the user input box is meant for
+pub enum InputType {
+    /// Text for the RIOT shell, sent via the diagnostic channel
+    Diagnostic,
+    /// A CoAP request, sent via the configuration channel
+    Coap {
+        method: Method,
+        path: String,
+        payload: Option<(Vec<u8>, ContentFormat)>,
+        /// False for `NON`, the device will not acknowledge the request
+        confirmable: bool,
+    },
+    /// Registers (`OBSERVE /path`) or cancels (`UNOBSERVE /path`) an observation
+    Observe { path: String, register: bool },
+    /// GETs every resource the device announced in /.well-known/core
+    Scan,
+    /// GETs the path `count` times, once a second, to measure round-trip times
+    Ping { path: String, count: usize },
+    /// Runs the history entry with this 1-based index, or lists recent entries
+    Rerun(Option<usize>),
+    /// Hand-crafted bytes, sent as-is via the configuration channel
+    Raw(Vec<u8>),
+    /// Input that looks like it is meant for Jelly but can not be sent
+    Invalid(String),
+}
+
+/// Classifies user input: a leading `/` is a CoAP GET, a leading
+/// `GET`, `POST`, `PUT` or `DELETE` followed by a path is a CoAP request
+/// with an optional payload, e.g. `PUT /config/interval 5000`. A payload
+/// of `@` followed by a local path sends the content of that file.
+/// A `?` suffix of the path is sent as Uri-Query options, e.g.
+/// `/.well-known/core?rt=temperature` only discovers matching resources.
+/// A request prefixed with `NON` is sent non-confirmable, e.g. `NON /riot/board`.
+/// `OBSERVE` and `UNOBSERVE` followed by a path start and stop an observation.
+/// `SCAN` requests every discovered resource.
+/// `PING` followed by a path and an optional count measures round-trip times,
+/// e.g. `PING /riot/board 5`, without a path it is left to the shell.
+/// `RERUN` lists recent commands and `RERUN <index>` sends one of them again.
+/// `RAW` followed by hex bytes is sent as a configuration frame without
+/// any encoding. Everything else goes to the shell.
+pub fn classify_input(input: &str) -> InputType {
+    if input.starts_with('/') {
+        return InputType::Coap {
+            method: Method::Get,
+            path: input.to_string(),
+            payload: None,
+            confirmable: true,
+        };
+    }
+
+    if input.trim().eq_ignore_ascii_case("RERUN") {
+        return InputType::Rerun(None);
+    }
+    if input.trim().eq_ignore_ascii_case("SCAN") {
+        return InputType::Scan;
+    }
+    let Some((verb, rest)) = input.trim_start().split_once(' ') else {
+        return InputType::Diagnostic;
+    };
+    if verb.eq_ignore_ascii_case("RERUN") {
+        return match rest.trim().parse() {
+            Ok(index) => InputType::Rerun(Some(index)),
+            Err(_) => InputType::Invalid(format!("'{}' is not a command index", rest.trim())),
+        };
+    }
+    if verb.eq_ignore_ascii_case("NON") {
+        return match classify_input(rest.trim_start()) {
+            InputType::Coap {
+                method,
+                path,
+                payload,
+                ..
+            } => InputType::Coap {
+                method,
+                path,
+                payload,
+                confirmable: false,
+            },
+            InputType::Invalid(err) => InputType::Invalid(err),
+            _ => InputType::Invalid("NON needs a CoAP request, e.g. 'NON /riot/board'".to_string()),
+        };
+    }
+    if verb.eq_ignore_ascii_case("RAW") {
+        return match parse_hex(rest) {
+            Ok(bytes) => InputType::Raw(bytes),
+            Err(err) => InputType::Invalid(err),
+        };
+    }
+    // RIOT's own ping takes an address instead of a path
+    if verb.eq_ignore_ascii_case("PING") && rest.trim_start().starts_with('/') {
+        let mut args = rest.split_whitespace();
+        let path = args.next().unwrap_or_default().to_string();
+        let count = match args.next().map(str::parse) {
+            None => DEFAULT_PING_COUNT,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => return InputType::Invalid("PING needs a positive count".to_string()),
+        };
+        return InputType::Ping { path, count };
+    }
+    let register = verb.eq_ignore_ascii_case("OBSERVE");
+    if register || verb.eq_ignore_ascii_case("UNOBSERVE") {
+        let path = rest.trim();
+        if !path.starts_with('/') {
+            return InputType::Invalid(format!("{verb} needs a path starting with '/'"));
+        }
+        return InputType::Observe {
+            path: path.to_string(),
+            register,
+        };
+    }
+    let method = match verb.to_ascii_uppercase().as_str() {
+        "GET" => Method::Get,
+        "POST" => Method::Post,
+        "PUT" => Method::Put,
+        "DELETE" => Method::Delete,
+        _ => return InputType::Diagnostic,
+    };
+    let rest = rest.trim();
+    if !rest.starts_with('/') {
+        return InputType::Diagnostic;
+    }
+
+    let (path, payload) = match rest.split_once(char::is_whitespace) {
+        Some((path, arg)) if !arg.trim().is_empty() => match read_payload(arg.trim()) {
+            Ok(payload) => (path, Some(payload)),
+            Err(err) => return InputType::Invalid(err),
+        },
+        _ => (rest, None),
+    };
+    InputType::Coap {
+        method,
+        path: path.to_string(),
+        payload,
+        confirmable: true,
+    }
+}
+
+/// `@file` payloads are read from disk, everything else is parsed inline
+fn read_payload(arg: &str) -> Result<(Vec<u8>, ContentFormat), String> {
+    let Some(file) = arg.strip_prefix('@') else {
+        return Ok(parse_payload(arg));
+    };
+    match std::fs::read(file) {
+        Ok(bytes) => Ok((bytes, ContentFormat::ApplicationOctetStream)),
+        Err(err) => Err(format!("Can not read payload from '{file}': {err}")),
+    }
+}
+
+/// Quoted strings are sent as text, valid EDN as CBOR and anything else as raw text
+fn parse_payload(arg: &str) -> (Vec<u8>, ContentFormat) {
+    if let Some(text) = arg.strip_prefix('"').and_then(|arg| arg.strip_suffix('"')) {
+        return (text.as_bytes().to_vec(), ContentFormat::TextPlain);
+    }
+    match StandaloneItem::parse(arg).map(|item| item.to_cbor()) {
+        Ok(Ok(cbor)) => (cbor, ContentFormat::ApplicationCBOR),
+        _ => (arg.as_bytes().to_vec(), ContentFormat::TextPlain),
+    }
+}
+
+/// Parses hex digits, whitespace between bytes is allowed, e.g. `42 01 00 01`
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err("A raw frame needs an even, non-zero number of hex digits".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| format!("'{byte}' is not a hex byte"))
+        })
+        .collect()
+}
+
+/// State of an incremental reverse search (Ctrl-R) through the command history
+pub struct ReverseSearch {
+    pub query: String,
+    /// Index of the currently matching history entry
+    pub found: Option<usize>,
+    /// Input to restore if the search gets cancelled
+    pub saved_input: String,
+}
+
+impl ReverseSearch {
+    pub fn new(saved_input: String) -> Self {
+        Self {
+            query: String::new(),
+            found: None,
+            saved_input,
+        }
+    }
+
+    /// Looks for the newest entry containing the query, starting at the
+    /// current match so that typing more characters keeps it if possible
+    pub fn refresh(&mut self, history: &[String]) {
+        let end = self.found.map_or(history.len(), |index| index + 1);
+        self.found = Self::search(history, &self.query, end);
+    }
+
+    /// Steps to the next older entry containing the query, if there is one
+    pub fn next_older(&mut self, history: &[String]) {
+        let end = self.found.unwrap_or(history.len());
+        if let Some(index) = Self::search(history, &self.query, end) {
+            self.found = Some(index);
+        }
+    }
+
+    fn search(history: &[String], query: &str, end: usize) -> Option<usize> {
+        history[..end]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+}
+
+/// Substring filter narrowing the diagnostic pane to matching lines, also
+/// used to search the CoAP requests and responses
+pub struct DiagnosticFilter {
+    pub needle: String,
+    pub ignore_case: bool,
+    /// Keys go to the filter instead of the user input while editing
+    pub editing: bool,
+}
+
+impl DiagnosticFilter {
+    pub fn new() -> Self {
+        Self {
+            needle: String::new(),
+            ignore_case: false,
+            editing: true,
+        }
+    }
+
+    /// Returns the byte ranges of all non-overlapping matches in `line`
+    pub fn find_matches(&self, line: &str) -> Vec<Range<usize>> {
+        let mut matches = vec![];
+        if self.needle.is_empty() {
+            return matches;
+        }
+        let mut start = 0;
+        while start < line.len() {
+            let end = start + self.needle.len();
+            let found = line.get(start..end).is_some_and(|candidate| {
+                if self.ignore_case {
+                    candidate.eq_ignore_ascii_case(&self.needle)
+                } else {
+                    candidate == self.needle
+                }
+            });
+            if found {
+                matches.push(start..end);
+                start = end;
+            } else {
+                start += line[start..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        matches
+    }
+}
+
+/// Spaces, slashes and dashes separate words, so path segments and flags
+/// can be skipped one at a time
+fn is_word_separator(c: char) -> bool {
+    c.is_whitespace() || c == '/' || c == '-'
+}
+
+/// Start of the word before `position`, skipping separators first
+pub fn previous_word_boundary(text: &str, position: usize) -> usize {
+    let before = text[..position].trim_end_matches(is_word_separator);
+    before.trim_end_matches(|c| !is_word_separator(c)).len()
+}
+
+/// End of the word after `position`, skipping separators first
+pub fn next_word_boundary(text: &str, position: usize) -> usize {
+    let after = text[position..].trim_start_matches(is_word_separator);
+    let word = after.trim_start_matches(|c| !is_word_separator(c));
+    text.len() - word.len()
+}
+
+/// Start of the character before `position` as the user perceives it, so
+/// emoji and accented letters made of several code points stay intact
+pub fn previous_grapheme_boundary(text: &str, position: usize) -> usize {
+    text[..position]
+        .graphemes(true)
+        .next_back()
+        .map_or(position, |grapheme| position - grapheme.len())
+}
+
+/// End of the character after `position`, see `previous_grapheme_boundary`
+pub fn next_grapheme_boundary(text: &str, position: usize) -> usize {
+    text[position..]
+        .graphemes(true)
+        .next()
+        .map_or(position, |grapheme| position + grapheme.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_boundaries_skip_multiple_spaces() {
+        let text = "GET   /riot   board";
+        assert_eq!(previous_word_boundary(text, text.len()), 14);
+        assert_eq!(previous_word_boundary(text, 14), 7);
+        assert_eq!(previous_word_boundary(text, 7), 0);
+        assert_eq!(previous_word_boundary(text, 0), 0);
+
+        assert_eq!(next_word_boundary(text, 0), 3);
+        assert_eq!(next_word_boundary(text, 3), 11);
+        assert_eq!(next_word_boundary(text, 11), text.len());
+        assert_eq!(next_word_boundary(text, text.len()), text.len());
+    }
+
+    #[test]
+    fn word_boundaries_stop_at_path_segments() {
+        let text = "/sensors/temp-0/value";
+        assert_eq!(previous_word_boundary(text, text.len()), 16);
+        assert_eq!(previous_word_boundary(text, 16), 14);
+        assert_eq!(previous_word_boundary(text, 14), 9);
+        assert_eq!(previous_word_boundary(text, 9), 1);
+        assert_eq!(previous_word_boundary(text, 1), 0);
+
+        assert_eq!(next_word_boundary(text, 0), 8);
+        assert_eq!(next_word_boundary(text, 8), 13);
+        assert_eq!(next_word_boundary(text, 13), 15);
+        assert_eq!(next_word_boundary(text, 15), text.len());
+    }
+
+    #[test]
+    fn cursor_moves_over_multi_byte_characters() {
+        // Two, three and four bytes, and an emoji made of two code points
+        let text = "ö日🪼👍🏽";
+        let mut position = 0;
+        let mut steps = vec![];
+        while position < text.len() {
+            position = next_grapheme_boundary(text, position);
+            steps.push(position);
+        }
+        assert_eq!(steps, vec![2, 5, 9, 17]);
+        assert_eq!(next_grapheme_boundary(text, text.len()), text.len());
+
+        for expected in [9, 5, 2, 0] {
+            position = previous_grapheme_boundary(text, position);
+            assert_eq!(position, expected);
+        }
+        assert_eq!(previous_grapheme_boundary(text, 0), 0);
+    }
+
+    #[test]
+    fn deleting_a_grapheme_keeps_valid_utf8() {
+        let mut text = "a日b".to_string();
+        let end = 4;
+        let start = previous_grapheme_boundary(&text, end);
+        text.drain(start..end);
+        assert_eq!(text, "ab");
+    }
+
+    #[test]
+    fn combining_characters_stay_with_their_letter() {
+        // o followed by a combining diaeresis
+        let text = "o\u{308}x";
+        assert_eq!(next_grapheme_boundary(text, 0), 3);
+        assert_eq!(previous_grapheme_boundary(text, 3), 0);
+    }
+
+    #[test]
+    fn word_boundaries_of_separators_only() {
+        assert_eq!(previous_word_boundary("  / ", 4), 0);
+        assert_eq!(next_word_boundary("  / ", 0), 4);
+    }
+}
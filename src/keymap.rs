@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyModifiers;
+
+/// Everything the user can trigger with a (re-mappable) key
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    Submit,
+    InsertNewline,
+    DeleteBackward,
+    DeleteWordBackward,
+    DeleteToStart,
+    DeleteToEnd,
+    Yank,
+    CursorLeft,
+    CursorRight,
+    InputStart,
+    InputEnd,
+    WordLeft,
+    WordRight,
+    HistoryPrevious,
+    HistoryNext,
+    Complete,
+    JumpToBottom,
+    ReverseSearch,
+    Filter,
+    ToggleShellDecoding,
+    ToggleFrameLog,
+    CycleFrameFilter,
+    ToggleRawSerial,
+    ToggleStatistics,
+    ToggleResourceTree,
+    SwitchScrollTarget,
+    ShrinkLeftPane,
+    GrowLeftPane,
+    ToggleCompactCbor,
+    ToggleErrorsOnly,
+    ToggleCollapseRepeats,
+    ToggleEcho,
+    SaveScreen,
+    SaveSession,
+    RefreshInfo,
+    Clear,
+    PageUp,
+    PageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    SelectNext,
+    SelectPrevious,
+    CopyResponse,
+    SelectLineUp,
+    SelectLineDown,
+    CopySelection,
+    ToggleHexdump,
+    ToggleOptions,
+    SearchRequests,
+    NextMatch,
+    PreviousMatch,
+}
+
+/// Name used in the keymap file and the default binding of every action
+const DEFAULT_BINDINGS: &[(Action, &str, &str)] = &[
+    (Action::Quit, "quit", "esc"),
+    (Action::Submit, "submit", "enter"),
+    // Most terminals only report shift+enter as plain enter
+    (Action::InsertNewline, "insert_newline", "alt+enter"),
+    (Action::DeleteBackward, "delete_backward", "backspace"),
+    (Action::DeleteWordBackward, "delete_word_backward", "ctrl+w"),
+    (Action::DeleteToStart, "delete_to_start", "ctrl+u"),
+    (Action::DeleteToEnd, "delete_to_end", "ctrl+k"),
+    (Action::Yank, "yank", "ctrl+y"),
+    (Action::CursorLeft, "cursor_left", "left"),
+    (Action::CursorRight, "cursor_right", "right"),
+    (Action::InputStart, "input_start", "ctrl+a"),
+    (Action::InputEnd, "input_end", "ctrl+e"),
+    (Action::WordLeft, "word_left", "ctrl+left"),
+    (Action::WordRight, "word_right", "ctrl+right"),
+    (Action::HistoryPrevious, "history_previous", "up"),
+    (Action::HistoryNext, "history_next", "down"),
+    (Action::Complete, "complete", "tab"),
+    (Action::JumpToBottom, "jump_to_bottom", "ctrl+g"),
+    (Action::ReverseSearch, "reverse_search", "ctrl+r"),
+    (Action::Filter, "filter", "ctrl+f"),
+    (Action::ToggleShellDecoding, "toggle_shell_decoding", "f2"),
+    (Action::ToggleFrameLog, "toggle_frame_log", "f3"),
+    (Action::CycleFrameFilter, "cycle_frame_filter", "alt+d"),
+    (Action::ToggleRawSerial, "toggle_raw_serial", "alt+r"),
+    (Action::ToggleStatistics, "toggle_statistics", "f9"),
+    (Action::ToggleResourceTree, "toggle_resource_tree", "f10"),
+    (Action::SwitchScrollTarget, "switch_scroll_target", "f4"),
+    (Action::ShrinkLeftPane, "shrink_left_pane", "alt+left"),
+    (Action::GrowLeftPane, "grow_left_pane", "alt+right"),
+    (Action::ToggleCompactCbor, "toggle_compact_cbor", "f5"),
+    (Action::ToggleErrorsOnly, "toggle_errors_only", "f6"),
+    (
+        Action::ToggleCollapseRepeats,
+        "toggle_collapse_repeats",
+        "f7",
+    ),
+    (Action::ToggleEcho, "toggle_echo", "alt+e"),
+    (Action::SaveScreen, "save_screen", "f12"),
+    (Action::SaveSession, "save_session", "f11"),
+    (Action::RefreshInfo, "refresh_info", "f8"),
+    (Action::Clear, "clear", "ctrl+l"),
+    (Action::PageUp, "page_up", "pageup"),
+    (Action::PageDown, "page_down", "pagedown"),
+    (Action::ScrollToTop, "scroll_to_top", "home"),
+    (Action::ScrollToBottom, "scroll_to_bottom", "end"),
+    (Action::SelectNext, "select_next", "alt+j"),
+    (Action::SelectPrevious, "select_previous", "alt+k"),
+    (Action::CopyResponse, "copy_response", "alt+y"),
+    (Action::SelectLineUp, "select_line_up", "shift+up"),
+    (Action::SelectLineDown, "select_line_down", "shift+down"),
+    (Action::CopySelection, "copy_selection", "alt+c"),
+    (Action::ToggleHexdump, "toggle_hexdump", "alt+x"),
+    (Action::ToggleOptions, "toggle_options", "alt+o"),
+    (Action::SearchRequests, "search_requests", "alt+f"),
+    (Action::NextMatch, "next_match", "alt+n"),
+    (Action::PreviousMatch, "previous_match", "alt+p"),
+];
+
+type Key = (KeyCode, KeyModifiers);
+
+/// Maps keys to actions, based on the defaults and an optional TOML file like:
+///
+/// ```toml
+/// scroll_to_top = "alt+g"
+/// page_down = ["pagedown", "ctrl+d"]
+/// ```
+///
+/// Actions listed in the file lose their default bindings. A character
+/// without ctrl or alt can no longer be typed once it is bound.
+pub struct KeyMap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl KeyMap {
+    /// Loads the keymap from `path`, or from the default location if it exists.
+    /// Returns the keymap and a list of problems found in the file.
+    pub fn load(path: Option<&Path>) -> (Self, Vec<String>) {
+        let mut warnings = vec![];
+        let mut configured: Vec<(Action, Vec<String>)> = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, _, key)| (*action, vec![key.to_string()]))
+            .collect();
+
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| config_file("keymap.toml").filter(|path| path.exists()));
+        if let Some(path) = path {
+            match fs::read_to_string(&path).map(|content| content.parse::<toml::Table>()) {
+                Ok(Ok(table)) => apply_table(&table, &mut configured, &mut warnings),
+                Ok(Err(err)) => warnings.push(format!("{}: {}", path.display(), err.message())),
+                Err(err) => warnings.push(format!("{}: {err}", path.display())),
+            }
+        }
+
+        let bindings = bind(configured, &mut warnings);
+        (Self { bindings }, warnings)
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        // Shift is already part of upper case characters
+        let modifiers = match key.code {
+            KeyCode::Char(_) => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        self.bindings.get(&(key.code, modifiers)).copied()
+    }
+}
+
+/// Location of a file in Jelly's configuration directory, e.g. ~/.config/jelly
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("jelly").join(name))
+}
+
+fn action_name(action: Action) -> &'static str {
+    DEFAULT_BINDINGS
+        .iter()
+        .find(|(known, _, _)| *known == action)
+        .map_or("unknown", |(_, name, _)| name)
+}
+
+fn bind(
+    configured: Vec<(Action, Vec<String>)>,
+    warnings: &mut Vec<String>,
+) -> HashMap<Key, Action> {
+    let mut bindings = HashMap::new();
+    for (action, keys) in configured {
+        for name in keys {
+            let Some(key) = parse_key(&name) else {
+                warnings.push(format!("Unknown key '{name}' for {}", action_name(action)));
+                continue;
+            };
+            if let Some(existing) = bindings.get(&key) {
+                warnings.push(format!(
+                    "'{name}' is bound to {} and {}, keeping {}",
+                    action_name(*existing),
+                    action_name(action),
+                    action_name(*existing)
+                ));
+                continue;
+            }
+            // Shift alone still types the character, it does not count for `action`
+            let (code, modifiers) = key;
+            if matches!(code, KeyCode::Char(_)) && (modifiers - KeyModifiers::SHIFT).is_empty() {
+                warnings.push(format!(
+                    "'{name}' is bound to {}, it can no longer be typed",
+                    action_name(action)
+                ));
+            }
+            bindings.insert(key, action);
+        }
+    }
+    bindings
+}
+
+fn apply_table(
+    table: &toml::Table,
+    configured: &mut [(Action, Vec<String>)],
+    warnings: &mut Vec<String>,
+) {
+    for (name, value) in table {
+        let Some((action, _, _)) = DEFAULT_BINDINGS.iter().find(|(_, known, _)| known == name)
+        else {
+            warnings.push(format!("Unknown action '{name}' in keymap"));
+            continue;
+        };
+        let keys = match value {
+            toml::Value::String(key) => vec![key.clone()],
+            toml::Value::Array(keys) => keys
+                .iter()
+                .filter_map(|key| key.as_str().map(str::to_string))
+                .collect(),
+            _ => {
+                warnings.push(format!("Expected a key or a list of keys for '{name}'"));
+                continue;
+            }
+        };
+        if let Some((_, bound)) = configured.iter_mut().find(|(known, _)| known == action) {
+            *bound = keys;
+        }
+    }
+}
+
+/// Parses keys like `esc`, `f2`, `ctrl+g` or `alt+shift+pageup`
+fn parse_key(name: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = name.split('+').collect();
+    let key = parts.pop()?;
+    for modifier in parts {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let code = match key.to_ascii_lowercase().as_str() {
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        lower => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(number) => KeyCode::F(number),
+            None => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        },
+    };
+    Some((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bindings and warnings for a keymap file with `content`
+    fn load(content: &str) -> (HashMap<Key, Action>, Vec<String>) {
+        let mut warnings = vec![];
+        let mut configured: Vec<(Action, Vec<String>)> = DEFAULT_BINDINGS
+            .iter()
+            .map(|(action, _, key)| (*action, vec![key.to_string()]))
+            .collect();
+        apply_table(&content.parse().unwrap(), &mut configured, &mut warnings);
+        let bindings = bind(configured, &mut warnings);
+        (bindings, warnings)
+    }
+
+    #[test]
+    fn defaults_have_no_warnings() {
+        let (bindings, warnings) = load("");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let key = (KeyCode::Char('g'), KeyModifiers::CONTROL);
+        assert_eq!(bindings.get(&key), Some(&Action::JumpToBottom));
+    }
+
+    #[test]
+    fn bare_character_warns() {
+        let (bindings, warnings) = load("scroll_to_top = \"g\"");
+        assert_eq!(
+            warnings,
+            ["'g' is bound to scroll_to_top, it can no longer be typed"]
+        );
+        let key = (KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(bindings.get(&key), Some(&Action::ScrollToTop));
+
+        let (_, warnings) = load("scroll_to_top = \"alt+g\"");
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn conflicting_binding_keeps_the_first() {
+        let (bindings, warnings) = load("toggle_frame_log = \"ctrl+g\"");
+        assert_eq!(
+            warnings,
+            ["'ctrl+g' is bound to jump_to_bottom and toggle_frame_log, keeping jump_to_bottom"]
+        );
+        let key = (KeyCode::Char('g'), KeyModifiers::CONTROL);
+        assert_eq!(bindings.get(&key), Some(&Action::JumpToBottom));
+    }
+}
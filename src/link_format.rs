@@ -0,0 +1,181 @@
+use std::iter::zip;
+
+/// One link of a CoRE link-format (RFC 6690) document like `/.well-known/core`
+pub struct LinkEntry {
+    /// Target of the link without the angle brackets, e.g. `/riot/board`
+    pub path: String,
+    /// Attributes in order of appearance, values are kept as written,
+    /// including their quotes, so they can be displayed unchanged
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+/// Parses a link-format payload. Devices send all kinds of almost valid
+/// documents, so this never fails: a link without `<` is taken as its path
+/// verbatim and separators inside quotes or angle brackets are ignored.
+pub fn parse_link_format(payload: &[u8]) -> Vec<LinkEntry> {
+    let text = String::from_utf8_lossy(payload);
+    split_outside_quotes(&text, ',')
+        .into_iter()
+        .filter(|link| !link.trim().is_empty())
+        .map(|link| {
+            let mut parts = split_outside_quotes(link, ';').into_iter();
+            let target = parts.next().unwrap_or_default().trim();
+            let path = target
+                .strip_prefix('<')
+                .map_or(target, |target| target.trim_end_matches('>'));
+            let attributes = parts
+                .map(|attribute| match attribute.split_once('=') {
+                    Some((key, value)) => (key.trim().to_string(), Some(value.to_string())),
+                    None => (attribute.trim().to_string(), None),
+                })
+                .collect();
+            LinkEntry {
+                path: path.to_string(),
+                attributes,
+            }
+        })
+        .collect()
+}
+
+/// One row of the resource tree, either a link or a path segment that
+/// only groups the links below it
+pub struct TreeRow {
+    pub depth: usize,
+    pub segment: String,
+    /// Index into the links the tree was built from
+    pub link: Option<usize>,
+}
+
+/// Orders the links by path and flattens them into rows of a tree with one
+/// level per path segment, so `/sensors/temp` and `/sensors/hum` end up
+/// below a `sensors` row
+pub fn resource_tree(links: &[LinkEntry]) -> Vec<TreeRow> {
+    let segments = |link: &LinkEntry| -> Vec<String> {
+        link.path
+            .trim_start_matches('/')
+            .split('/')
+            .map(str::to_string)
+            .collect()
+    };
+    let mut sorted: Vec<(Vec<String>, usize)> = links
+        .iter()
+        .enumerate()
+        .map(|(index, link)| (segments(link), index))
+        .collect();
+    sorted.sort();
+
+    let mut rows = vec![];
+    let mut previous: &[String] = &[];
+    for (path, index) in &sorted {
+        let common = zip(previous, path).take_while(|(a, b)| a == b).count();
+        // Groups missing so far, a link with the same path as a group does not need one
+        for (depth, segment) in path.iter().enumerate().take(path.len() - 1).skip(common) {
+            rows.push(TreeRow {
+                depth,
+                segment: segment.clone(),
+                link: None,
+            });
+        }
+        rows.push(TreeRow {
+            depth: path.len() - 1,
+            segment: path.last().cloned().unwrap_or_default(),
+            link: Some(*index),
+        });
+        previous = path;
+    }
+    rows
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut bracketed = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '<' if !quoted => bracketed = true,
+            '>' if !quoted => bracketed = false,
+            c if c == separator && !quoted && !bracketed => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(links: &[LinkEntry]) -> Vec<&str> {
+        links.iter().map(|link| link.path.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_links_with_attributes() {
+        let links = parse_link_format(b"</riot/board>;ct=0;obs,</sha256>");
+        assert_eq!(paths(&links), vec!["/riot/board", "/sha256"]);
+        assert_eq!(
+            links[0].attributes,
+            vec![
+                ("ct".to_string(), Some("0".to_string())),
+                ("obs".to_string(), None)
+            ]
+        );
+        assert!(links[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn link_without_angle_brackets_is_taken_verbatim() {
+        let links = parse_link_format(b"/riot/board;rt=\"board\",</sha256>");
+        assert_eq!(paths(&links), vec!["/riot/board", "/sha256"]);
+        assert_eq!(links[0].attributes[0].0, "rt");
+    }
+
+    #[test]
+    fn link_without_closing_bracket_keeps_its_path() {
+        let links = parse_link_format(b"</riot/board;ct=0");
+        assert_eq!(paths(&links), vec!["/riot/board;ct=0"]);
+    }
+
+    #[test]
+    fn commas_inside_quotes_and_brackets_do_not_split() {
+        let links = parse_link_format(b"</a,b>;title=\"x, y\";rt=\"c\",</d>");
+        assert_eq!(paths(&links), vec!["/a,b", "/d"]);
+        assert_eq!(
+            links[0].attributes[0],
+            ("title".to_string(), Some("\"x, y\"".to_string()))
+        );
+    }
+
+    #[test]
+    fn empty_links_are_skipped() {
+        let links = parse_link_format(b",</a>,, ,");
+        assert_eq!(paths(&links), vec!["/a"]);
+        assert!(parse_link_format(b"").is_empty());
+    }
+
+    #[test]
+    fn resource_tree_groups_by_segment() {
+        let links = parse_link_format(b"</sensors/temp>,</riot/board>,</sensors/hum>,</sensors>");
+        let tree = resource_tree(&links);
+        let rows: Vec<(usize, &str, Option<usize>)> = tree
+            .iter()
+            .map(|row| (row.depth, row.segment.as_str(), row.link))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (0, "riot", None),
+                (1, "board", Some(1)),
+                (0, "sensors", Some(3)),
+                (1, "hum", Some(2)),
+                (1, "temp", Some(0)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Number of diagnostic characters after connecting that are inspected
+const SAMPLE_SIZE: usize = 512;
+/// Below this many characters the garbage ratio is not meaningful yet
+const MIN_SAMPLE_SIZE: usize = 64;
+/// Share of unprintable characters that hints at a wrong baud rate
+const MAX_GARBAGE_RATIO: f32 = 0.2;
+/// Framing errors this soon after connecting count towards the warning
+const SETTLE_TIME: Duration = Duration::from_secs(10);
+const MAX_EARLY_FRAMING_ERRORS: usize = 3;
+/// CoAP resets and timeouts are counted over this rolling window
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Heuristic to detect a serial link running at the wrong baud rate,
+/// which shows up as mostly unprintable bytes and broken SLIP frames.
+/// Also keeps track of CoAP level failures for the footer.
+pub struct LinkHealth {
+    connected: Instant,
+    checked: usize,
+    garbage: usize,
+    framing_errors: usize,
+    early_framing_errors: usize,
+    failures: VecDeque<Instant>,
+}
+
+impl LinkHealth {
+    pub fn new() -> Self {
+        Self {
+            connected: Instant::now(),
+            checked: 0,
+            garbage: 0,
+            framing_errors: 0,
+            early_framing_errors: 0,
+            failures: VecDeque::new(),
+        }
+    }
+
+    pub fn add_diagnostic(&mut self, text: &str) {
+        for c in text.chars().take(SAMPLE_SIZE - self.checked) {
+            self.checked += 1;
+            // Invalid UTF-8 was already replaced with U+FFFD by the read thread
+            if c == char::REPLACEMENT_CHARACTER || (c.is_control() && !c.is_whitespace()) {
+                self.garbage += 1;
+            }
+        }
+    }
+
+    pub fn add_framing_error(&mut self) {
+        self.framing_errors += 1;
+        if self.connected.elapsed() < SETTLE_TIME {
+            self.early_framing_errors += 1;
+        }
+    }
+
+    pub fn framing_errors(&self) -> usize {
+        self.framing_errors
+    }
+
+    /// Records a CoAP reset or timeout, a sign the device is not keeping up
+    pub fn add_failure(&mut self) {
+        while self
+            .failures
+            .front()
+            .is_some_and(|failure| failure.elapsed() > FAILURE_WINDOW)
+        {
+            self.failures.pop_front();
+        }
+        self.failures.push_back(Instant::now());
+    }
+
+    /// Resets and timeouts within the last `FAILURE_WINDOW`
+    pub fn recent_failures(&self) -> usize {
+        self.failures
+            .iter()
+            .filter(|failure| failure.elapsed() <= FAILURE_WINDOW)
+            .count()
+    }
+
+    fn garbage_ratio(&self) -> Option<f32> {
+        (self.checked >= MIN_SAMPLE_SIZE).then(|| self.garbage as f32 / self.checked as f32)
+    }
+
+    /// Returns a warning for the user if the link looks like a baud mismatch
+    pub fn warning(&self) -> Option<String> {
+        let ratio = self.garbage_ratio().unwrap_or(0.0);
+        if ratio <= MAX_GARBAGE_RATIO && self.early_framing_errors < MAX_EARLY_FRAMING_ERRORS {
+            return None;
+        }
+        Some(format!(
+            "possible baud mismatch ({:.0}% garbage, {} framing errors), try --baud 115200, 57600 or 9600",
+            ratio * 100.0,
+            self.framing_errors
+        ))
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+/// While data keeps arriving it is synced to disk once per interval
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Appends the raw diagnostic bytes to a file, exactly as they arrived.
+/// Writes are unbuffered, the read thread is never shut down cleanly.
+pub struct LogFile {
+    /// Dropped after the first failed write, the stream goes on without it
+    file: Option<File>,
+    last_sync: Instant,
+}
+
+impl LogFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Some(file),
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// Returns a warning if writing failed, only the first time
+    pub fn write(&mut self, data: &[u8]) -> Option<String> {
+        let file = self.file.as_mut()?;
+        let mut result = file.write_all(data);
+        if result.is_ok() && self.last_sync.elapsed() > SYNC_INTERVAL {
+            result = file.sync_data();
+            self.last_sync = Instant::now();
+        }
+        let err = result.err()?;
+        self.file = None;
+        Some(format!(
+            "Writing the log file failed, logging stopped: {err}"
+        ))
+    }
+}
@@ -1,33 +1,275 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use std::thread;
 use std::time::Duration;
 
+use clap::Parser;
+use clap::ValueEnum;
+use log_file::LogFile;
+use once::run_once;
+use ps::PsSort;
+use replay::Replay;
+use serialport::DataBits;
+use serialport::FlowControl;
+use slipmux::parse_endpoint;
 use slipmux::read_thread;
+use slipmux::ConnectionEvent;
+use slipmux::Endpoint;
+use slipmux::FrameInfo;
+use slipmux::SerialConfig;
+use theme::ThemeName;
 use tui::show;
 
+mod aliases;
+mod input;
+mod keymap;
+mod link_format;
+mod link_health;
+mod log_file;
+mod once;
+mod ping;
+mod ps;
+mod replay;
+mod request;
+mod scan;
+mod shell_output;
 mod slipmux;
+mod statistics;
+mod theme;
+mod timestamp;
 mod tui;
 
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Seconds without new data after which a pane shows when it was last updated, 0 disables
+    #[arg(long, default_value_t = 10)]
+    stale_after: u64,
+
+    /// Seconds to wait for a CoAP response before marking the request as timed out
+    #[arg(long, default_value_t = 5)]
+    coap_timeout: u64,
+
+    /// Lines of diagnostic output to keep, older ones are dropped
+    #[arg(long, default_value_t = 10_000)]
+    scrollback: usize,
+
+    /// Order of the threads in the /config/ps table
+    #[arg(long, value_enum, default_value_t = PsSort::None)]
+    ps_sort: PsSort,
+
+    /// Upper limit of redraws per second. Lower values save CPU on slow
+    /// terminals or over SSH, but input and new output show up later.
+    /// Everything arriving in between is drawn with the next frame.
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    max_fps: u32,
+
+    /// Quit on the first Esc or Ctrl+C even while CoAP requests are still awaiting a response
+    #[arg(long)]
+    force_quit: bool,
+
+    /// TOML file mapping actions to keys, defaults to ~/.config/jelly/keymap.toml
+    #[arg(long)]
+    keymap: Option<PathBuf>,
+
+    /// TOML file mapping aliases to commands, defaults to ~/.config/jelly/aliases.toml
+    #[arg(long)]
+    aliases: Option<PathBuf>,
+
+    /// When to use colors, `auto` disables them if NO_COLOR is set
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Colors for highlights, `light` is readable on white backgrounds
+    #[arg(long, value_enum, default_value_t = ThemeName::Dark)]
+    theme: ThemeName,
+
+    /// Plain ASCII status markers instead of emoji, implied by a non UTF-8 locale
+    #[arg(long)]
+    ascii: bool,
+
+    /// File the session is saved to with F11 and on exit, as JSON if it ends in .json
+    #[arg(long)]
+    save: Option<PathBuf>,
+
+    /// strftime format of timestamps in the saved session
+    #[arg(long, default_value = "%Y-%m-%dT%H:%M:%S%.3f%:z", value_parser = timestamp::parse_format)]
+    timestamp_format: String,
+
+    /// Use the local timezone instead of UTC for timestamps
+    #[arg(long)]
+    local_time: bool,
+
+    /// Keep bytes outside of slipmux frames, e.g. boot messages, for a hexdump
+    /// view toggled with Alt+R
+    #[arg(long)]
+    raw_serial: bool,
+
+    /// Append every diagnostic byte received to this file, unprocessed
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Play back a session saved as JSON instead of connecting to a device
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Speak slipmux over a socket instead of the serial device, e.g. to an
+    /// emulator: tcp://host:port or unix:///path/to/socket
+    #[arg(long, value_parser = parse_endpoint, value_name = "URL")]
+    connect: Option<Endpoint>,
+
+    /// Send a single CoAP request, print the response payload and exit. Exits
+    /// with 1 on an error response or timeout and 2 if nothing could be sent.
+    #[arg(long, conflicts_with = "replay", value_name = "REQUEST")]
+    once: Option<String>,
+
+    /// Baud rate of the serial device
+    #[arg(long, default_value_t = 115200, value_parser = clap::value_parser!(u32).range(1..))]
+    baud: u32,
+
+    /// Data bits per character
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(5..=8))]
+    data_bits: u8,
+
+    /// Flow control of the serial device
+    #[arg(long, value_enum, default_value_t = FlowControlMode::None)]
+    flow_control: FlowControlMode,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FlowControlMode {
+    None,
+    Software,
+    Hardware,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Cli {
+    pub fn use_color(&self) -> bool {
+        match self.color {
+            // See https://no-color.org, an empty value does not count
+            ColorMode::Auto => env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// The socket given with `--connect`, otherwise the serial device
+    pub fn endpoint(&self) -> Endpoint {
+        if let Some(endpoint) = &self.connect {
+            return endpoint.clone();
+        }
+        Endpoint::Serial(SerialConfig {
+            path: "/dev/ttyACM0".to_string(),
+            baud_rate: self.baud,
+            data_bits: match self.data_bits {
+                5 => DataBits::Five,
+                6 => DataBits::Six,
+                7 => DataBits::Seven,
+                _ => DataBits::Eight,
+            },
+            flow_control: match self.flow_control {
+                FlowControlMode::None => FlowControl::None,
+                FlowControlMode::Software => FlowControl::Software,
+                FlowControlMode::Hardware => FlowControl::Hardware,
+            },
+        })
+    }
+
+    pub fn use_ascii(&self) -> bool {
+        // The first non-empty variable decides, like it does for the C library
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(env::var_os)
+            .find(|value| !value.is_empty());
+        self.ascii
+            || locale.is_some_and(|locale| {
+                let locale = locale.to_string_lossy().to_ascii_lowercase();
+                !locale.contains("utf-8") && !locale.contains("utf8")
+            })
+    }
+}
+
 fn main() {
+    let cli = Cli::parse();
+    let replay = match cli.replay.as_deref().map(Replay::load).transpose() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("Failed to load the session to replay: {err}");
+            process::exit(1);
+        }
+    };
+
+    let log_file = match cli.log_file.as_deref().map(LogFile::open).transpose() {
+        Ok(log_file) => log_file,
+        Err(err) => {
+            eprintln!("Failed to open the log file: {err}");
+            process::exit(1);
+        }
+    };
+
     let (diagnostic_tx, diagnostic_rx): (Sender<String>, Receiver<String>) = mpsc::channel();
     let (configuration_tx, configuration_rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) =
         mpsc::channel();
     let (packet_tx, packet_rx): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = mpsc::channel();
+    let (frame_tx, frame_rx): (Sender<FrameInfo>, Receiver<FrameInfo>) = mpsc::channel();
+    let (connection_tx, connection_rx): (Sender<ConnectionEvent>, Receiver<ConnectionEvent>) =
+        mpsc::channel();
 
     //let conf_tx = configuration_tx.clone();
 
-    let mut port = serialport::new("/dev/ttyACM0", 115200)
-        .open()
-        .expect("Error");
-    let _ = port.set_timeout(Duration::from_secs(60));
-    let read_port = port.try_clone().unwrap();
-    let write_port = port.try_clone().unwrap();
-
-    let _ =
-        thread::spawn(move || read_thread(read_port, diagnostic_tx, configuration_tx, packet_tx));
-    show(write_port, diagnostic_rx, configuration_rx, packet_rx);
+    // A replay does not touch the device, the unused senders keep the channels open
+    let _idle_senders = if replay.is_some() {
+        Some((
+            connection_tx,
+            diagnostic_tx,
+            configuration_tx,
+            packet_tx,
+            frame_tx,
+        ))
+    } else {
+        let endpoint = cli.endpoint();
+        let _ = thread::spawn(move || {
+            read_thread(
+                endpoint,
+                connection_tx,
+                diagnostic_tx,
+                configuration_tx,
+                packet_tx,
+                frame_tx,
+                log_file,
+            )
+        });
+        None
+    };
+    if let Some(input) = &cli.once {
+        process::exit(run_once(
+            input,
+            &cli.endpoint().to_string(),
+            Duration::from_secs(cli.coap_timeout),
+            &connection_rx,
+            &configuration_rx,
+        ));
+    }
+    show(
+        &cli,
+        replay,
+        connection_rx,
+        diagnostic_rx,
+        configuration_rx,
+        packet_rx,
+        frame_rx,
+    );
     //let ui_loop =
     //    thread::spawn(move || print_thread(write_port, diagnostic_rx, configuration_rx, packet_rx));
 
@@ -0,0 +1,137 @@
+use std::io;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use coap_lite::CoapOption;
+use coap_lite::CoapRequest;
+use coap_lite::MessageClass;
+use coap_lite::MessageType;
+use coap_lite::Packet;
+
+use crate::input::classify_input;
+use crate::input::InputType;
+use crate::request::set_target;
+use crate::request::Request;
+use crate::slipmux::send_configuration;
+use crate::slipmux::ConnectionEvent;
+use crate::slipmux::WritePort;
+
+/// The device answered with a 4.xx or 5.xx code, or not at all
+const EXIT_FAILED: i32 = 1;
+/// The request could not be sent, e.g. there is no device or the input is not CoAP
+const EXIT_UNSENT: i32 = 2;
+
+/// How often the response channel is checked for timeouts and retransmissions
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends a single CoAP request once the device is connected and writes the
+/// response payload to stdout. Returns the exit code for the process.
+pub fn run_once(
+    input: &str,
+    device: &str,
+    timeout: Duration,
+    connection_channel: &Receiver<ConnectionEvent>,
+    configuration_channel: &Receiver<Vec<u8>>,
+) -> i32 {
+    let InputType::Coap {
+        method,
+        path,
+        payload,
+        confirmable,
+    } = classify_input(input)
+    else {
+        eprintln!("'{input}' is not a CoAP request, e.g. '/riot/board' or 'PUT /path 5'");
+        return EXIT_UNSENT;
+    };
+    let mut coap: CoapRequest<String> = CoapRequest::new();
+    coap.set_method(method);
+    set_target(&mut coap, &path);
+    if let Some((payload, content_format)) = payload {
+        coap.message.set_content_format(content_format);
+        coap.message.payload = payload;
+    }
+    if !confirmable {
+        coap.message.header.set_type(MessageType::NonConfirmable);
+    }
+    coap.message.set_token(vec![1]);
+    coap.message.header.message_id = 1;
+    coap.message.add_option(CoapOption::Block2, vec![0x05]);
+
+    let mut port = loop {
+        match connection_channel.recv_timeout(timeout) {
+            Ok(ConnectionEvent::Connected(port)) => break port,
+            Ok(ConnectionEvent::Disconnected) => {}
+            Ok(ConnectionEvent::Rejected(err)) => {
+                eprintln!("{device} rejected the serial settings: {err}");
+                return EXIT_UNSENT;
+            }
+            Err(_) => {
+                eprintln!("{device} did not show up within {}s", timeout.as_secs());
+                return EXIT_UNSENT;
+            }
+        }
+    };
+
+    let mut request = Request::new(coap);
+    let mut message_id = 1;
+    write_packet(&mut port, &request.coap.message);
+    loop {
+        if request.check_timeout(timeout) {
+            eprintln!("No response within {}s", timeout.as_secs());
+            return EXIT_FAILED;
+        }
+        if let Some(packet) = request.retransmit() {
+            write_packet(&mut port, &packet);
+        }
+        let data = match configuration_channel.recv_timeout(POLL_INTERVAL) {
+            Ok(data) => data,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("The connection to {device} closed before a response arrived");
+                return EXIT_FAILED;
+            }
+        };
+        let Ok(response) = Packet::from_bytes(&data) else {
+            continue;
+        };
+        request.on_acknowledgement(&response);
+        if response.get_token() != request.coap.message.get_token() {
+            continue;
+        }
+        let mut follow_up = false;
+        for mut next in request.on_response(response) {
+            if matches!(
+                next.header.get_type(),
+                MessageType::Confirmable | MessageType::NonConfirmable
+            ) {
+                message_id += 1;
+                next.header.message_id = message_id;
+                request.track(next.clone());
+                follow_up = true;
+            }
+            write_packet(&mut port, &next);
+        }
+        if follow_up {
+            continue;
+        }
+        let Some(response) = &request.coap.response else {
+            continue;
+        };
+        _ = io::stdout().write_all(&response.message.payload);
+        return match response.message.header.code {
+            MessageClass::Response(code) if !code.is_error() => 0,
+            code => {
+                eprintln!("The device answered with {code}");
+                EXIT_FAILED
+            }
+        };
+    }
+}
+
+fn write_packet(port: &mut WritePort, packet: &Packet) {
+    let data = send_configuration(packet);
+    let _ = port.write(&data);
+    let _ = port.flush();
+}
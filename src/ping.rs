@@ -0,0 +1,126 @@
+use std::fmt::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::request::Request;
+
+/// Time between the requests of a ping
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// GETs the same resource at a fixed interval to measure round-trip times
+pub struct Ping {
+    pub path: String,
+    count: usize,
+    next_at: Instant,
+    /// Index in the request log of every request sent so far
+    sent: Vec<usize>,
+}
+
+impl Ping {
+    pub fn new(path: String, count: usize) -> Self {
+        Self {
+            path,
+            count,
+            next_at: Instant::now(),
+            sent: vec![],
+        }
+    }
+
+    /// True if it is time for the next request
+    pub fn is_due(&self) -> bool {
+        self.sent.len() < self.count && Instant::now() >= self.next_at
+    }
+
+    /// Remembers the request just sent and schedules the next one
+    pub fn add_request(&mut self, index: usize) {
+        self.sent.push(index);
+        self.next_at = Instant::now() + INTERVAL;
+    }
+
+    /// True once every request was sent and got a response or timed out
+    pub fn is_done(&self, requests: &[Request]) -> bool {
+        self.sent.len() == self.count
+            && self
+                .sent
+                .iter()
+                .all(|&index| requests[index].coap.response.is_some() || requests[index].timed_out)
+    }
+
+    /// Counts and round-trip times of the requests sent so far, for the status line
+    pub fn fmt_progress(&self, requests: &[Request]) -> String {
+        format!("PING {}: {}", self.path, self.fmt_statistics(requests))
+    }
+
+    /// Every round-trip time followed by the statistics
+    pub fn fmt_summary(&self, requests: &[Request]) -> String {
+        // When writing to a String `write!` will never fail.
+        // Therefore the Result is ignored with `_ = write!()`.
+        let mut out = String::new();
+        _ = writeln!(out, "PING {}, {} requests", self.path, self.count);
+        for (seq, &index) in self.sent.iter().enumerate() {
+            let request = &requests[index];
+            match (&request.coap.response, round_trip(request)) {
+                (Some(response), Some(rtt)) => {
+                    _ = writeln!(
+                        out,
+                        "seq={seq} {} time={:.1} ms",
+                        response.message.header.code,
+                        millis(rtt)
+                    )
+                }
+                _ => _ = writeln!(out, "seq={seq} timed out"),
+            }
+        }
+        _ = writeln!(out, "{}", self.fmt_statistics(requests));
+        out
+    }
+
+    fn fmt_statistics(&self, requests: &[Request]) -> String {
+        let rtts: Vec<Duration> = self
+            .sent
+            .iter()
+            .filter_map(|&index| round_trip(&requests[index]))
+            .collect();
+        let lost = self
+            .sent
+            .iter()
+            .filter(|&&index| requests[index].timed_out)
+            .count();
+        let mut out = format!(
+            "{} sent, {} received, {lost} lost",
+            self.sent.len(),
+            rtts.len()
+        );
+        let (Some(min), Some(max)) = (rtts.iter().min(), rtts.iter().max()) else {
+            return out;
+        };
+        let average = rtts.iter().sum::<Duration>() / rtts.len() as u32;
+        // Mean difference between consecutive round trips
+        let jitter = if rtts.len() > 1 {
+            rtts.windows(2)
+                .map(|pair| pair[0].abs_diff(pair[1]))
+                .sum::<Duration>()
+                / (rtts.len() - 1) as u32
+        } else {
+            Duration::ZERO
+        };
+        _ = write!(
+            out,
+            ", min/avg/max/jitter {:.1}/{:.1}/{:.1}/{:.1} ms",
+            millis(*min),
+            millis(average),
+            millis(*max),
+            millis(jitter)
+        );
+        out
+    }
+}
+
+fn round_trip(request: &Request) -> Option<Duration> {
+    request.coap.response.as_ref()?;
+    request.received_at?.duration_since(request.sent_at).ok()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+/// Order of the threads in the `/config/ps` table
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PsSort {
+    /// As reported by the device
+    None,
+    /// Most used stack first
+    Used,
+    /// Least free stack first
+    Free,
+    Name,
+}
+
+/// One thread of the `/config/ps` response
+pub struct Thread {
+    pub name: String,
+    pub stack_size: u32,
+    pub stack_used: u32,
+    pub stack_start: u32,
+}
+
+impl Thread {
+    pub fn stack_free(&self) -> u32 {
+        self.stack_size.saturating_sub(self.stack_used)
+    }
+
+    /// Estimated from the start and usage, 52 bytes are the thread control block
+    pub fn stack_pointer(&self) -> u32 {
+        self.stack_start
+            .wrapping_add(self.stack_size)
+            .wrapping_add(52)
+            .wrapping_sub(self.stack_used)
+    }
+}
+
+/// Parses the binary `/config/ps` payload: per thread a zero terminated name
+/// followed by stack size, used stack and stack start as little endian u32.
+/// A truncated last thread is dropped instead of failing the whole table.
+pub fn parse_ps(payload: &[u8]) -> Vec<Thread> {
+    let mut threads = vec![];
+    let mut rest = payload;
+    while let Some(name_end) = rest.iter().position(|&byte| byte == 0) {
+        let name = String::from_utf8_lossy(&rest[..name_end]).to_string();
+        let Some(fields) = rest.get(name_end + 1..name_end + 13) else {
+            break;
+        };
+        let field =
+            |index: usize| u32::from_le_bytes(fields[index * 4..index * 4 + 4].try_into().unwrap());
+        threads.push(Thread {
+            name,
+            stack_size: field(0),
+            stack_used: field(1),
+            stack_start: field(2),
+        });
+        rest = &rest[name_end + 13..];
+    }
+    threads
+}
+
+/// Renders the threads as an aligned table
+pub fn fmt_ps_table(mut threads: Vec<Thread>, sort: PsSort) -> String {
+    match sort {
+        PsSort::None => {}
+        PsSort::Used => threads.sort_by_key(|thread| std::cmp::Reverse(thread.stack_used)),
+        PsSort::Free => threads.sort_by_key(Thread::stack_free),
+        PsSort::Name => threads.sort_by(|a, b| a.name.cmp(&b.name)),
+    }
+    // When writing to a String `write!` will never fail.
+    // Therefore the Result is ignored with `_ = write!()`.
+    let mut out = String::new();
+    _ = writeln!(
+        out,
+        "{:<20}|{:<5}|{:<5}|{:<5}|{:<10}|{:<10}|",
+        "name", "stack", "used", "free", "start", "SP"
+    );
+    for thread in threads {
+        _ = writeln!(
+            out,
+            "{:<20}|{:<5}|{:<5}|{:<5}|{:#010x}|{:#010x}|",
+            thread.name,
+            thread.stack_size,
+            thread.stack_used,
+            thread.stack_free(),
+            thread.stack_start,
+            thread.stack_pointer()
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/config/ps` payload of a board running idle, main and a shell thread
+    fn sample_payload() -> Vec<u8> {
+        let mut payload = vec![];
+        for (name, size, used, start) in [
+            ("idle", 256u32, 136u32, 0x2000_0300u32),
+            ("main", 1536, 1032, 0x2000_0400),
+            ("shell", 2048, 420, 0x2000_0a00),
+        ] {
+            payload.extend_from_slice(name.as_bytes());
+            payload.push(0);
+            for field in [size, used, start] {
+                payload.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+        payload
+    }
+
+    fn names(table: &str) -> Vec<&str> {
+        table
+            .lines()
+            .skip(1)
+            .map(|line| line.split('|').next().unwrap_or_default().trim())
+            .collect()
+    }
+
+    #[test]
+    fn parses_every_thread() {
+        let threads = parse_ps(&sample_payload());
+        assert_eq!(threads.len(), 3);
+        assert_eq!(threads[1].name, "main");
+        assert_eq!(threads[1].stack_size, 1536);
+        assert_eq!(threads[1].stack_used, 1032);
+        assert_eq!(threads[1].stack_free(), 504);
+        assert_eq!(threads[1].stack_start, 0x2000_0400);
+        assert_eq!(threads[1].stack_pointer(), 0x2000_0400 + 1536 + 52 - 1032);
+    }
+
+    #[test]
+    fn truncated_thread_is_dropped() {
+        let payload = sample_payload();
+        let threads = parse_ps(&payload[..payload.len() - 1]);
+        assert_eq!(threads.len(), 2);
+        assert!(parse_ps(&[]).is_empty());
+    }
+
+    #[test]
+    fn table_is_aligned() {
+        let table = fmt_ps_table(parse_ps(&sample_payload()), PsSort::None);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            "name                |stack|used |free |start     |SP        |"
+        );
+        assert_eq!(
+            lines[2],
+            "main                |1536 |1032 |504  |0x20000400|0x2000062c|"
+        );
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+    }
+
+    #[test]
+    fn table_is_sorted() {
+        let sorted = |sort| fmt_ps_table(parse_ps(&sample_payload()), sort);
+        assert_eq!(names(&sorted(PsSort::None)), vec!["idle", "main", "shell"]);
+        assert_eq!(names(&sorted(PsSort::Used)), vec!["main", "shell", "idle"]);
+        assert_eq!(names(&sorted(PsSort::Free)), vec!["idle", "main", "shell"]);
+        assert_eq!(names(&sorted(PsSort::Name)), vec!["idle", "main", "shell"]);
+    }
+}
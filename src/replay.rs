@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde_json::Value;
+
+use crate::input::parse_hex;
+
+/// Something that happened in a recorded session
+pub enum ReplayEntry {
+    Diagnostic {
+        command: Option<String>,
+        text: String,
+    },
+    Request(Vec<u8>),
+    Response(Vec<u8>),
+}
+
+/// A session saved as JSON, played back at its recorded pace
+pub struct Replay {
+    entries: VecDeque<(Duration, ReplayEntry)>,
+    started: Instant,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let json: Value = serde_json::from_str(&content)
+            .map_err(|err| format!("not a session saved as JSON: {err}"))?;
+        let records = json
+            .as_array()
+            .ok_or("expected a list of session entries")?;
+
+        let mut recorded = vec![];
+        for (index, record) in records.iter().enumerate() {
+            let unix_ms = record["unix_ms"]
+                .as_u64()
+                .ok_or(format!("entry {index} has no unix_ms"))?;
+            let frame = || {
+                let hex = record["frame"]
+                    .as_str()
+                    .ok_or(format!("entry {index} has no frame"))?;
+                parse_hex(hex).map_err(|err| format!("entry {index}: {err}"))
+            };
+            let entry = match record["kind"].as_str() {
+                Some("diagnostic") => ReplayEntry::Diagnostic {
+                    command: record["summary"]
+                        .as_str()
+                        .filter(|command| !command.is_empty())
+                        .map(str::to_string),
+                    text: record["text"].as_str().unwrap_or_default().to_string(),
+                },
+                Some("request") => ReplayEntry::Request(frame()?),
+                Some("response") => ReplayEntry::Response(frame()?),
+                _ => return Err(format!("entry {index} has an unknown kind")),
+            };
+            recorded.push((unix_ms, entry));
+        }
+        // Saved sessions are sorted, but do not rely on it for the timing
+        recorded.sort_by_key(|(unix_ms, _)| *unix_ms);
+        let first = recorded.first().map_or(0, |(unix_ms, _)| *unix_ms);
+        let entries = recorded
+            .into_iter()
+            .map(|(unix_ms, entry)| (Duration::from_millis(unix_ms - first), entry))
+            .collect();
+
+        Ok(Self {
+            entries,
+            started: Instant::now(),
+        })
+    }
+
+    /// Returns the next entry once its time has come
+    pub fn next_due(&mut self) -> Option<ReplayEntry> {
+        if self.entries.front()?.0 > self.started.elapsed() {
+            return None;
+        }
+        self.entries.pop_front().map(|(_, entry)| entry)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
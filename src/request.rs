@@ -0,0 +1,458 @@
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use coap_lite::block_handler::BlockValue;
+use coap_lite::CoapOption;
+use coap_lite::CoapRequest;
+use coap_lite::CoapResponse;
+use coap_lite::MessageClass;
+use coap_lite::MessageType;
+use coap_lite::Packet;
+use coap_lite::ResponseType;
+use ratatui::style::Style;
+use ratatui::text::Line;
+
+/// Payloads larger than one block of this size exponent (64 bytes) are
+/// uploaded via Block1, a whole block has to fit into a single SLIP frame
+const BLOCK1_SIZE_EXPONENT: u8 = 2;
+/// Older notifications of an observed resource are dropped
+const MAX_NOTIFICATIONS: usize = 50;
+/// Retransmission parameters for confirmable messages, see RFC 7252 section 4.8
+const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_RETRANSMIT: u8 = 4;
+
+/// A CoAP request sent to the device, together with what became of it
+pub struct Request {
+    pub coap: CoapRequest<String>,
+    pub sent: Instant,
+    /// Wall clock times for the session export, `sent` is for the timeout
+    pub sent_at: SystemTime,
+    pub received_at: Option<SystemTime>,
+    pub timed_out: bool,
+    /// Payload of the Block2 blocks received so far
+    pub partial_payload: Vec<u8>,
+    /// Full payload of a Block1 upload and how much of it was sent so far
+    upload: Vec<u8>,
+    uploaded: usize,
+    /// True while an Observe registration of this request is active
+    pub observing: bool,
+    /// Every response of an observation with the time it arrived, oldest first
+    pub notifications: Vec<(SystemTime, Packet)>,
+    /// Latest packet sent for this request, retransmitted until it is acknowledged
+    last_sent: Packet,
+    acknowledged: bool,
+    pub retransmissions: u8,
+    retransmit_at: Instant,
+    retransmit_timeout: Duration,
+    /// Show the payload as a hexdump instead of decoding it by content format
+    pub hexdump: bool,
+    /// List every option of the request and its response
+    pub show_options: bool,
+    /// The device answered 2.03 Valid and the payload is the one of an
+    /// earlier response with the same ETag
+    cached: bool,
+    /// Response formatted for display, whether CBOR was compact and whether it
+    /// was a hexdump, formatting large CBOR or link-format payloads on every
+    /// frame is too expensive
+    rendered: Option<(bool, bool, Vec<Line<'static>>)>,
+}
+
+impl Request {
+    /// Keeps only the first block of a large payload in `coap.message`,
+    /// the rest follows whenever the device asks for it
+    pub fn new(mut coap: CoapRequest<String>) -> Self {
+        let mut upload = vec![];
+        let mut uploaded = 0;
+        let first = BlockValue {
+            num: 0,
+            more: true,
+            size_exponent: BLOCK1_SIZE_EXPONENT,
+        };
+        if coap.message.payload.len() > first.size() {
+            upload = std::mem::take(&mut coap.message.payload);
+            uploaded = first.size();
+            coap.message.payload = upload[..uploaded].to_vec();
+            coap.message.add_option_as(CoapOption::Block1, first);
+        }
+        // Observe 0 registers, the device answers with a stream of notifications
+        let observing = matches!(coap.message.get_observe_value(), Some(Ok(0)));
+        let last_sent = coap.message.clone();
+        let retransmit_timeout = initial_retransmit_timeout();
+        Self {
+            acknowledged: last_sent.header.get_type() != MessageType::Confirmable,
+            last_sent,
+            retransmissions: 0,
+            retransmit_at: Instant::now() + retransmit_timeout,
+            retransmit_timeout,
+            coap,
+            sent: Instant::now(),
+            sent_at: SystemTime::now(),
+            received_at: None,
+            timed_out: false,
+            partial_payload: vec![],
+            upload,
+            uploaded,
+            observing,
+            notifications: vec![],
+            hexdump: false,
+            show_options: false,
+            cached: false,
+            rendered: None,
+        }
+    }
+
+    /// Restarts the retransmission of confirmable messages for a follow-up
+    /// packet, e.g. the next block of a transfer
+    pub fn track(&mut self, packet: Packet) {
+        self.acknowledged = packet.header.get_type() != MessageType::Confirmable;
+        self.last_sent = packet;
+        self.retransmissions = 0;
+        self.retransmit_timeout = initial_retransmit_timeout();
+        self.retransmit_at = Instant::now() + self.retransmit_timeout;
+    }
+
+    /// Stops retransmitting once an ACK or RST with the message id of the last
+    /// packet arrives, empty ACKs carry no token to match the response by
+    pub fn on_acknowledgement(&mut self, packet: &Packet) {
+        if matches!(
+            packet.header.get_type(),
+            MessageType::Acknowledgement | MessageType::Reset
+        ) && packet.header.message_id == self.last_sent.header.message_id
+        {
+            self.acknowledged = true;
+        }
+    }
+
+    /// Returns the last packet again if it is due for a retransmission,
+    /// doubling the timeout every time
+    pub fn retransmit(&mut self) -> Option<Packet> {
+        if self.acknowledged
+            || self.retransmissions >= MAX_RETRANSMIT
+            || Instant::now() < self.retransmit_at
+        {
+            return None;
+        }
+        self.retransmissions += 1;
+        self.retransmit_timeout *= 2;
+        self.retransmit_at = Instant::now() + self.retransmit_timeout;
+        self.sent = Instant::now();
+        Some(self.last_sent.clone())
+    }
+
+    /// Bytes sent and total size while a Block1 upload is in progress
+    pub fn upload_progress(&self) -> Option<(usize, usize)> {
+        (!self.upload.is_empty() && self.coap.response.is_none())
+            .then_some((self.uploaded, self.upload.len()))
+    }
+
+    /// Marks the request as timed out if no response arrived within `timeout`
+    /// of the last transmission and no retransmission is left.
+    /// Returns true if the state changed.
+    pub fn check_timeout(&mut self, timeout: Duration) -> bool {
+        let retransmitting = !self.acknowledged && self.retransmissions < MAX_RETRANSMIT;
+        if self.coap.response.is_none()
+            && !self.timed_out
+            && !retransmitting
+            && self.sent.elapsed() > timeout
+        {
+            self.timed_out = true;
+            return true;
+        }
+        false
+    }
+
+    /// Handles a response and returns the packets to send in reply: the ACK
+    /// for a confirmable response, or a reset once the observation got cancelled
+    /// so the device stops sending notifications, and the request for the next
+    /// block if the transfer is not complete yet.
+    pub fn on_response(&mut self, response: Packet) -> Vec<Packet> {
+        let mut replies = vec![];
+        if response.header.get_type() == MessageType::Confirmable {
+            let cancelled = !self.observing
+                && !self.notifications.is_empty()
+                && response.get_observe_value().is_some();
+            let mut empty = Packet::new();
+            empty.header.code = MessageClass::Empty;
+            empty.header.message_id = response.header.message_id;
+            empty.header.set_type(if cancelled {
+                MessageType::Reset
+            } else {
+                MessageType::Acknowledgement
+            });
+            replies.push(empty);
+        }
+        replies.extend(self.store_response(response));
+        replies
+    }
+
+    /// Stores the response, or one block of it if the payload is split via Block2.
+    /// Returns the request for the next block if the transfer is not complete yet.
+    fn store_response(&mut self, mut response: Packet) -> Option<Packet> {
+        // A response implies the device got the request
+        self.acknowledged = true;
+        if response.header.code == MessageClass::Response(ResponseType::Continue) {
+            return self.next_upload_block(&response);
+        }
+        if self.observing || !self.notifications.is_empty() {
+            self.on_notification(response);
+            return None;
+        }
+        let block = response
+            .get_first_option_as::<BlockValue>(CoapOption::Block2)
+            .and_then(Result::ok);
+        if let Some(block) = block {
+            // Duplicates and blocks out of order would corrupt the payload
+            if usize::from(block.num) * block.size() != self.partial_payload.len() {
+                return None;
+            }
+            if block.more {
+                self.partial_payload.extend(&response.payload);
+                self.sent = Instant::now();
+                let mut next = self.coap.message.clone();
+                next.clear_option(CoapOption::Block2);
+                next.add_option_as(
+                    CoapOption::Block2,
+                    BlockValue {
+                        num: block.num + 1,
+                        more: false,
+                        size_exponent: block.size_exponent,
+                    },
+                );
+                return Some(next);
+            }
+            self.partial_payload.append(&mut response.payload);
+            response.payload = std::mem::take(&mut self.partial_payload);
+        }
+        self.complete(response);
+        None
+    }
+
+    /// Appends a notification of an observed resource
+    fn on_notification(&mut self, response: Packet) {
+        if self.notifications.len() == MAX_NOTIFICATIONS {
+            self.notifications.remove(0);
+        }
+        self.notifications
+            .push((SystemTime::now(), response.clone()));
+        self.complete(response);
+    }
+
+    /// Builds the request carrying the next Block1 block after the device
+    /// acknowledged the previous one with 2.31 Continue
+    fn next_upload_block(&mut self, response: &Packet) -> Option<Packet> {
+        let acknowledged = response
+            .get_first_option_as::<BlockValue>(CoapOption::Block1)
+            .and_then(Result::ok)?;
+        // The device may ask for smaller blocks, the offset stays the same
+        let size = acknowledged.size();
+        if self.uploaded >= self.upload.len() || !self.uploaded.is_multiple_of(size) {
+            return None;
+        }
+        let end = (self.uploaded + size).min(self.upload.len());
+        let mut next = self.coap.message.clone();
+        next.payload = self.upload[self.uploaded..end].to_vec();
+        next.clear_option(CoapOption::Block1);
+        next.add_option_as(
+            CoapOption::Block1,
+            BlockValue {
+                num: u16::try_from(self.uploaded / size).ok()?,
+                more: end < self.upload.len(),
+                size_exponent: acknowledged.size_exponent,
+            },
+        );
+        self.uploaded = end;
+        self.sent = Instant::now();
+        Some(next)
+    }
+
+    /// Stores the full response, late responses still replace the timed out marker
+    pub fn complete(&mut self, response: Packet) {
+        self.coap.response = Some(CoapResponse { message: response });
+        self.received_at = Some(SystemTime::now());
+        self.rendered = None;
+    }
+
+    /// Fills in the payload of an earlier response after the device confirmed
+    /// with 2.03 Valid that the resource did not change since
+    pub fn reuse_payload(&mut self, earlier: &Packet) {
+        let Some(response) = &mut self.coap.response else {
+            return;
+        };
+        response.message.payload = earlier.payload.clone();
+        if let Some(content_format) = earlier.get_content_format() {
+            response.message.set_content_format(content_format);
+        }
+        self.cached = true;
+        self.rendered = None;
+    }
+
+    /// Formats the response with `render` unless a matching one is cached
+    pub fn render_response(
+        &mut self,
+        compact: bool,
+        render: impl Fn(&Packet, bool, bool) -> Vec<Line<'static>>,
+    ) {
+        let Some(response) = &self.coap.response else {
+            return;
+        };
+        let hexdump = self.hexdump;
+        if self
+            .rendered
+            .as_ref()
+            .is_some_and(|(cached, cached_hexdump, _)| {
+                *cached == compact && *cached_hexdump == hexdump
+            })
+        {
+            return;
+        }
+        if self.notifications.is_empty() {
+            let mut lines = render(&response.message, compact, hexdump);
+            if let Some(received_at) = self.received_at {
+                add_latency(&mut lines, received_at, self.sent_at);
+            }
+            if let (true, Some(status)) = (self.cached, lines.first_mut()) {
+                status.push_span(" unchanged, cached payload");
+            }
+            self.rendered = Some((compact, hexdump, lines));
+            return;
+        }
+        let mut lines = vec![];
+        let mut previous = self.sent_at;
+        for (index, (time, notification)) in self.notifications.iter().enumerate() {
+            let after = time.duration_since(self.sent_at).unwrap_or_default();
+            lines.push(Line::styled(
+                format!(
+                    "Notification {} after {:.1}s",
+                    index + 1,
+                    after.as_secs_f32()
+                ),
+                Style::new().italic(),
+            ));
+            // Later notifications are timed against the one before
+            let mut rendered = render(notification, compact, hexdump);
+            add_latency(&mut rendered, *time, previous);
+            previous = *time;
+            lines.extend(rendered);
+        }
+        self.rendered = Some((compact, hexdump, lines));
+    }
+
+    /// The response as formatted by the last `render_response`
+    pub fn rendered_response(&self) -> Option<&[Line<'static>]> {
+        self.rendered.as_ref().map(|(_, _, lines)| lines.as_slice())
+    }
+
+    /// False for non-confirmable requests, the device does not acknowledge
+    /// them and may not answer at all
+    pub fn is_confirmable(&self) -> bool {
+        self.coap.message.header.get_type() == MessageType::Confirmable
+    }
+
+    /// True if the device answered with a 4.xx or 5.xx response code
+    pub fn is_error(&self) -> bool {
+        match &self.coap.response {
+            Some(response) => matches!(
+                response.message.header.code,
+                MessageClass::Response(code) if code.is_error()
+            ),
+            None => false,
+        }
+    }
+}
+
+/// Sets the Uri-Path and, for a suffix like `?rt=temperature&if=sensor`, one
+/// Uri-Query option per `&` separated part
+pub fn set_target(coap: &mut CoapRequest<String>, target: &str) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    coap.set_path(path);
+    for part in query.split('&').filter(|part| !part.is_empty()) {
+        coap.message
+            .add_option(CoapOption::UriQuery, part.as_bytes().to_vec());
+    }
+}
+
+/// Appends the time between `since` and `time` to the status line of a response
+fn add_latency(lines: &mut [Line<'static>], time: SystemTime, since: SystemTime) {
+    let latency = time.duration_since(since).unwrap_or_default();
+    if let Some(status) = lines.first_mut() {
+        status.push_span(format!(" ({:.1} ms)", latency.as_secs_f64() * 1000.0));
+    }
+}
+
+/// `ACK_TIMEOUT` stretched by a random factor between 1 and 1.5, so requests
+/// sent together are not retransmitted together
+fn initial_retransmit_timeout() -> Duration {
+    let jitter = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    ACK_TIMEOUT + ACK_TIMEOUT.mul_f64(f64::from(jitter % 1000) / 2000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(message_type: MessageType) -> Request {
+        let mut coap: CoapRequest<String> = CoapRequest::new();
+        coap.message.header.set_type(message_type);
+        let mut request = Request::new(coap);
+        // Sent a while ago, so any timeout has passed
+        request.sent -= Duration::from_secs(1);
+        request
+    }
+
+    #[test]
+    fn confirmable_request_does_not_time_out_while_retransmitting() {
+        let mut request = request(MessageType::Confirmable);
+        assert!(!request.check_timeout(Duration::ZERO));
+
+        request.retransmissions = MAX_RETRANSMIT;
+        assert!(request.check_timeout(Duration::ZERO));
+        assert!(request.timed_out);
+    }
+
+    #[test]
+    fn acknowledged_request_times_out() {
+        let mut request = request(MessageType::Confirmable);
+        let mut ack = Packet::new();
+        ack.header.set_type(MessageType::Acknowledgement);
+        ack.header.message_id = request.coap.message.header.message_id;
+        request.on_acknowledgement(&ack);
+        assert!(request.check_timeout(Duration::ZERO));
+    }
+
+    fn response(message_type: MessageType) -> Packet {
+        let mut response = Packet::new();
+        response.header.set_type(message_type);
+        response.header.code = MessageClass::Response(ResponseType::Content);
+        response.header.message_id = 0x1234;
+        response
+    }
+
+    #[test]
+    fn confirmable_response_is_acknowledged() {
+        let mut request = request(MessageType::Confirmable);
+        let replies = request.on_response(response(MessageType::Confirmable));
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].header.get_type(), MessageType::Acknowledgement);
+        assert_eq!(replies[0].header.code, MessageClass::Empty);
+        assert_eq!(replies[0].header.message_id, 0x1234);
+        assert!(request.coap.response.is_some());
+    }
+
+    #[test]
+    fn piggybacked_response_needs_no_reply() {
+        let mut request = request(MessageType::Confirmable);
+        assert!(request
+            .on_response(response(MessageType::Acknowledgement))
+            .is_empty());
+    }
+
+    #[test]
+    fn non_confirmable_request_times_out() {
+        let mut request = request(MessageType::NonConfirmable);
+        assert!(request.check_timeout(Duration::ZERO));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::request::Request;
+
+/// Requests of a scan awaiting a response at the same time, more would
+/// overwhelm small devices
+const MAX_IN_FLIGHT: usize = 2;
+
+/// GETs a list of resources, a few at a time, for a snapshot of the device
+pub struct Scan {
+    queue: VecDeque<String>,
+    /// Index in the request log of every request sent so far
+    sent: Vec<usize>,
+}
+
+impl Scan {
+    pub fn new(paths: Vec<String>) -> Self {
+        Self {
+            queue: paths.into(),
+            sent: vec![],
+        }
+    }
+
+    /// The next path to request, unless too many requests are still pending
+    pub fn next_path(&mut self, requests: &[Request]) -> Option<String> {
+        let pending = self
+            .sent
+            .iter()
+            .filter(|&&index| is_pending(&requests[index]))
+            .count();
+        if pending >= MAX_IN_FLIGHT {
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    /// Remembers the request just sent for the next path
+    pub fn add_request(&mut self, index: usize) {
+        self.sent.push(index);
+    }
+
+    /// True once every path got a response or timed out
+    pub fn is_done(&self, requests: &[Request]) -> bool {
+        self.queue.is_empty() && !self.sent.iter().any(|&index| is_pending(&requests[index]))
+    }
+
+    /// One row per resource with the response code and payload size
+    pub fn fmt_summary(&self, requests: &[Request]) -> String {
+        // When writing to a String `write!` will never fail.
+        // Therefore the Result is ignored with `_ = write!()`.
+        let mut out = String::new();
+        _ = writeln!(out, "SCAN of {} resources", self.sent.len());
+        _ = writeln!(out, "{:<30}|{:<24}|{:>8}", "path", "status", "bytes");
+        for &index in &self.sent {
+            let request = &requests[index];
+            let (status, size) = match &request.coap.response {
+                Some(response) => (
+                    response.message.header.code.to_string(),
+                    response.message.payload.len().to_string(),
+                ),
+                None => ("timed out".to_string(), String::new()),
+            };
+            _ = writeln!(
+                out,
+                "{:<30}|{status:<24}|{size:>8}",
+                format!("/{}", request.coap.get_path())
+            );
+        }
+        out
+    }
+}
+
+fn is_pending(request: &Request) -> bool {
+    request.coap.response.is_none() && !request.timed_out
+}
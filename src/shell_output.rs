@@ -0,0 +1,265 @@
+use std::fmt::Write;
+use std::time::SystemTime;
+
+/// Turns the raw text output of a shell command into a prettier representation
+type Formatter = fn(&str) -> String;
+
+/// Known RIOT shell commands and the formatter used for their output
+const FORMATTERS: &[(&str, Formatter)] =
+    &[("ifconfig", fmt_ifconfig), ("nib", fmt_nib), ("ps", fmt_ps)];
+
+/// Output of the diagnostic channel, attributed to the shell command that
+/// was most recently sent when the text arrived.
+pub struct DiagnosticOutput {
+    command: Option<String>,
+    /// The whole command as sent, for echoing it above the output
+    command_line: Option<String>,
+    text: String,
+    started: SystemTime,
+}
+
+impl DiagnosticOutput {
+    pub fn new(command_line: Option<&str>) -> Self {
+        // Only the command name is relevant for picking a formatter,
+        // `ifconfig 6 set chan 11` is still ifconfig.
+        let command = command_line
+            .and_then(|line| line.split_whitespace().next())
+            .map(str::to_string);
+        Self {
+            command,
+            command_line: command_line.map(|line| line.trim_end().to_string()),
+            text: String::new(),
+            started: SystemTime::now(),
+        }
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    pub fn command_line(&self) -> Option<&str> {
+        self.command_line.as_deref()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn started(&self) -> SystemTime {
+        self.started
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.matches('\n').count()
+    }
+
+    /// Removes up to `count` complete lines from the start, returns how many
+    pub fn drop_lines(&mut self, count: usize) -> usize {
+        let end = self
+            .text
+            .match_indices('\n')
+            .take(count)
+            .last()
+            .map_or(0, |(index, _)| index + 1);
+        let dropped = self.text[..end].matches('\n').count();
+        self.text.drain(..end);
+        dropped
+    }
+
+    /// Returns the output, pretty-rendered if `decode` is set and the command is known
+    pub fn render(&self, decode: bool) -> String {
+        match (decode, self.command.as_deref().and_then(formatter_for)) {
+            (true, Some(formatter)) => formatter(&self.text),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+pub fn formatter_for(command: &str) -> Option<Formatter> {
+    FORMATTERS
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map(|(_, formatter)| *formatter)
+}
+
+/// Collapses runs of identical lines into a single `(xN) line`. A trailing
+/// line without a newline may still grow, so it is never merged.
+pub fn collapse_repeats(text: &str) -> String {
+    let mut out = String::new();
+    let mut run: Option<(&str, usize)> = None;
+    for line in text.split_inclusive('\n') {
+        let Some(complete) = line.strip_suffix('\n') else {
+            push_run(&mut out, run.take());
+            out.push_str(line);
+            break;
+        };
+        match &mut run {
+            Some((previous, count)) if *previous == complete => *count += 1,
+            _ => push_run(&mut out, run.replace((complete, 1))),
+        }
+    }
+    push_run(&mut out, run);
+    out
+}
+
+fn push_run(out: &mut String, run: Option<(&str, usize)>) {
+    match run {
+        Some((line, 1)) => _ = writeln!(out, "{line}"),
+        Some((line, count)) => _ = writeln!(out, "(x{count}) {line}"),
+        None => {}
+    }
+}
+
+/// Re-aligns the `|` separated thread table printed by `ps`
+fn fmt_ps(text: &str) -> String {
+    let rows: Vec<Vec<&str>> = text
+        .lines()
+        .map(|line| {
+            if line.contains('|') {
+                line.split('|').map(str::trim).collect()
+            } else {
+                vec![line]
+            }
+        })
+        .collect();
+    fmt_table(text, &rows, " | ")
+}
+
+/// Aligns the whitespace separated columns printed by `nib`
+fn fmt_nib(text: &str) -> String {
+    let rows: Vec<Vec<&str>> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    fmt_table(text, &rows, " ")
+}
+
+/// Puts every attribute of an interface onto its own line
+fn fmt_ifconfig(text: &str) -> String {
+    // When writing to a String `write!` will never fail.
+    // Therefore the Result is ignored with `_ = write!()`.
+    let mut out = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(iface) = line.strip_prefix("Iface") {
+            let mut fields = iface.split("  ").map(str::trim).filter(|f| !f.is_empty());
+            _ = writeln!(out, "Iface {}", fields.next().unwrap_or_default());
+            for field in fields {
+                _ = writeln!(out, "  {field}");
+            }
+        } else if line.is_empty() {
+            _ = writeln!(out);
+        } else {
+            for field in line.split("  ").map(str::trim).filter(|f| !f.is_empty()) {
+                _ = writeln!(out, "  {field}");
+            }
+        }
+    }
+    keep_trailing_partial_line(text, out)
+}
+
+/// Pads every cell of a table to the widest cell of its column.
+/// Rows with a single cell are not considered part of the table.
+fn fmt_table(text: &str, rows: &[Vec<&str>], separator: &str) -> String {
+    let mut widths: Vec<usize> = vec![];
+    for row in rows.iter().filter(|row| row.len() > 1) {
+        for (column, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(column) {
+                Some(max) => *max = (*max).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in rows {
+        if row.len() > 1 {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            _ = writeln!(out, "{}", cells.join(separator).trim_end());
+        } else {
+            _ = writeln!(out, "{}", row.first().unwrap_or(&""));
+        }
+    }
+    keep_trailing_partial_line(text, out)
+}
+
+/// `lines()` swallows the information whether the last line is complete,
+/// the formatted output should not claim a newline that never arrived.
+fn keep_trailing_partial_line(text: &str, mut out: String) -> String {
+    if !text.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_lines_are_collapsed() {
+        assert_eq!(collapse_repeats("a\na\na\nb\na\n"), "(x3) a\nb\na\n");
+        assert_eq!(collapse_repeats("a\nb\n"), "a\nb\n");
+        assert_eq!(collapse_repeats(""), "");
+    }
+
+    #[test]
+    fn partial_last_line_is_not_collapsed() {
+        // The device may still send the rest of "a", e.g. "ab\n"
+        assert_eq!(collapse_repeats("a\na\na"), "(x2) a\na");
+        assert_eq!(collapse_repeats("a\n\n\n"), "a\n(x2) \n");
+    }
+
+    #[test]
+    fn ps_columns_are_aligned() {
+        let text = "\tpid | name | state\n\t  1 | idle | pending\n\t  2 | main | running\n";
+        assert_eq!(
+            fmt_ps(text),
+            "pid | name | state\n1   | idle | pending\n2   | main | running\n"
+        );
+    }
+
+    #[test]
+    fn nib_columns_are_aligned_and_partial_line_stays_partial() {
+        let text = "Address State Iface\nfe80::1 REACHABLE 6\nfe80::20 STALE 6";
+        assert_eq!(
+            fmt_nib(text),
+            "Address  State     Iface\nfe80::1  REACHABLE 6\nfe80::20 STALE     6"
+        );
+    }
+
+    #[test]
+    fn ifconfig_attributes_get_their_own_lines() {
+        let text =
+            "Iface  6  HWaddr: 2A:1F  Channel: 26\n          inet6 addr: fe80::1  scope: link\n\n";
+        assert_eq!(
+            fmt_ifconfig(text),
+            "Iface 6\n  HWaddr: 2A:1F\n  Channel: 26\n  inet6 addr: fe80::1\n  scope: link\n\n"
+        );
+    }
+
+    #[test]
+    fn known_commands_are_decoded() {
+        let mut output = DiagnosticOutput::new(Some("nib neigh"));
+        output.push_str("a b\nccc d\n");
+        assert_eq!(output.render(true), "a   b\nccc d\n");
+        assert_eq!(output.render(false), "a b\nccc d\n");
+
+        let mut output = DiagnosticOutput::new(Some("reboot"));
+        output.push_str("a b\nccc d\n");
+        assert_eq!(output.render(true), "a b\nccc d\n");
+    }
+}
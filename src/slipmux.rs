@@ -1,42 +1,285 @@
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
 use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
 
 use coap_lite::Packet;
 use serial_line_ip::Decoder;
 use serial_line_ip::Encoder;
-use serialport::SerialPort;
+use serialport::DataBits;
+use serialport::FlowControl;
+
+use crate::log_file::LogFile;
 
 const DIAGNOSTIC: u8 = 0x0a;
 const CONFIGURATION: u8 = 0xA9;
 
-pub fn send_diagnostic(text: &str) -> ([u8; 256], usize) {
-    let mut output: [u8; 256] = [0; 256];
-    let mut slip = Encoder::new();
-    let mut totals = slip.encode(&[DIAGNOSTIC], &mut output).unwrap();
-    totals += slip
-        .encode(text.as_bytes(), &mut output[totals.written..])
-        .unwrap();
-    totals += slip.finish(&mut output[totals.written..]).unwrap();
-    (output, totals.written)
+/// Write side of a connection to the device
+pub type WritePort = Box<dyn Write + Send>;
+type ReadPort = Box<dyn Read + Send>;
+
+/// Changes of the connection, reported by the read thread
+pub enum ConnectionEvent {
+    /// The device was (re)opened, writes go to the given port from now on
+    Connected(WritePort),
+    /// Reading failed, the device is gone until the next `Connected`
+    Disconnected,
+    /// The device refused the serial settings, retrying would not help
+    Rejected(String),
+}
+
+/// Which device to open and how
+#[derive(Clone)]
+pub struct SerialConfig {
+    pub path: String,
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub flow_control: FlowControl,
+}
+
+/// Where slipmux frames are exchanged with the device, a serial port or a
+/// stream socket, e.g. of an emulator
+#[derive(Clone)]
+pub enum Endpoint {
+    Serial(SerialConfig),
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Serial(config) => write!(f, "{}", config.path),
+            Endpoint::Tcp(address) => write!(f, "tcp://{address}"),
+            Endpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Parses `tcp://host:port` or `unix:///path/to/socket`
+pub fn parse_endpoint(url: &str) -> Result<Endpoint, String> {
+    if let Some(address) = url.strip_prefix("tcp://").filter(|rest| !rest.is_empty()) {
+        return Ok(Endpoint::Tcp(address.to_string()));
+    }
+    if let Some(path) = url.strip_prefix("unix://").filter(|rest| !rest.is_empty()) {
+        return Ok(Endpoint::Unix(PathBuf::from(path)));
+    }
+    Err(format!(
+        "'{url}' is neither tcp://host:port nor unix:///path/to/socket"
+    ))
+}
+
+/// Why an endpoint could not be opened
+enum OpenError {
+    /// The device refused the settings, retrying would not help
+    Rejected(String),
+    /// The device is not there (yet), retrying might help
+    Unavailable,
+}
+
+fn open(endpoint: &Endpoint) -> Result<(ReadPort, WritePort), OpenError> {
+    match endpoint {
+        Endpoint::Serial(config) => {
+            let port = serialport::new(&config.path, config.baud_rate)
+                .data_bits(config.data_bits)
+                .flow_control(config.flow_control)
+                .timeout(Duration::from_secs(60))
+                .open();
+            match port.and_then(|port| Ok((port.try_clone()?, port))) {
+                Ok((read_port, write_port)) => Ok((Box::new(read_port), Box::new(write_port))),
+                Err(err) if err.kind() == serialport::ErrorKind::InvalidInput => {
+                    Err(OpenError::Rejected(err.description))
+                }
+                Err(_) => Err(OpenError::Unavailable),
+            }
+        }
+        Endpoint::Tcp(address) => TcpStream::connect(address)
+            .and_then(|stream| Ok((stream.try_clone()?, stream)))
+            .map(|(read_port, write_port)| -> (ReadPort, WritePort) {
+                (Box::new(read_port), Box::new(write_port))
+            })
+            .map_err(|_| OpenError::Unavailable),
+        Endpoint::Unix(path) => UnixStream::connect(path)
+            .and_then(|stream| Ok((stream.try_clone()?, stream)))
+            .map(|(read_port, write_port)| -> (ReadPort, WritePort) {
+                (Box::new(read_port), Box::new(write_port))
+            })
+            .map_err(|_| OpenError::Unavailable),
+    }
 }
 
-pub fn send_configuration(packet: &Packet) -> ([u8; 256], usize) {
-    let mut output: [u8; 256] = [0; 256];
+/// What the read thread saw on the wire, for debugging the framing
+pub enum FrameInfo {
+    /// A complete frame with its leading type byte and total length
+    Frame(u8, usize),
+    /// Bytes that could not be decoded as SLIP
+    Error,
+    /// Bytes outside of slipmux frames, e.g. boot messages or garbage from a
+    /// wrong baud rate, for the raw serial view
+    Raw(Vec<u8>),
+}
+
+/// Type byte and decoded length of a frame encoded by one of the `send_*` functions
+pub fn encoded_frame_info(data: &[u8]) -> FrameInfo {
+    const ESC: u8 = 0xdb;
+    match data.get(1) {
+        // Leading and trailing END, every escaped byte takes two
+        Some(&frame_type) => FrameInfo::Frame(
+            frame_type,
+            data.len() - 2 - data.iter().filter(|&&byte| byte == ESC).count(),
+        ),
+        None => FrameInfo::Error,
+    }
+}
+
+/// Name of the slipmux channel a frame with the given type byte belongs to
+pub fn channel_name(frame_type: u8) -> &'static str {
+    match frame_type {
+        DIAGNOSTIC => "diagnostic",
+        CONFIGURATION => "configuration",
+        _ => "packet",
+    }
+}
+
+/// Longest text sent in a single diagnostic frame, so small devices can take it
+const MAX_DIAGNOSTIC_CHUNK: usize = 128;
+
+/// Encodes text for the shell, long text is split over several frames.
+/// The device reads them as one stream, so splitting inside a character is fine.
+pub fn send_diagnostic(text: &str) -> Vec<Vec<u8>> {
+    text.as_bytes()
+        .chunks(MAX_DIAGNOSTIC_CHUNK)
+        .map(|chunk| {
+            // Leading and trailing END, the type byte and every byte escaped
+            let mut output = vec![0; 2 * chunk.len() + 3];
+            let mut slip = Encoder::new();
+            let mut totals = slip.encode(&[DIAGNOSTIC], &mut output).unwrap();
+            totals += slip.encode(chunk, &mut output[totals.written..]).unwrap();
+            totals += slip.finish(&mut output[totals.written..]).unwrap();
+            output.truncate(totals.written);
+            output
+        })
+        .collect()
+}
+
+pub fn send_configuration(packet: &Packet) -> Vec<u8> {
+    // Slipmux has no size limit, a payload may well exceed `Packet::MAX_SIZE`
+    send_configuration_bytes(&packet.to_bytes_unlimited().unwrap())
+}
+
+pub fn send_configuration_bytes(bytes: &[u8]) -> Vec<u8> {
+    // Leading and trailing END, the type byte and every byte escaped
+    let mut output = vec![0; 2 * bytes.len() + 3];
     let mut slip = Encoder::new();
     let mut totals = slip.encode(&[CONFIGURATION], &mut output).unwrap();
-    totals += slip
-        .encode(&packet.to_bytes().unwrap(), &mut output[totals.written..])
-        .unwrap();
+    totals += slip.encode(bytes, &mut output[totals.written..]).unwrap();
     totals += slip.finish(&mut output[totals.written..]).unwrap();
-    (output, totals.written)
+    output.truncate(totals.written);
+    output
 }
 
+/// Opens the endpoint and forwards everything read from it. Whenever the
+/// device disappears, e.g. because the board was unplugged, it is reopened as
+/// soon as it is back.
 pub fn read_thread(
-    mut read_port: Box<dyn SerialPort>,
+    endpoint: Endpoint,
+    connection_channel: Sender<ConnectionEvent>,
     diagnostic_channel: Sender<String>,
     configuration_channel: Sender<Vec<u8>>,
     packet_channel: Sender<Vec<u8>>,
+    frame_channel: Sender<FrameInfo>,
+    mut log_file: Option<LogFile>,
+) {
+    const MIN_BACKOFF: Duration = Duration::from_millis(100);
+    const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let (read_port, write_port) = match open(&endpoint) {
+            Ok(ports) => ports,
+            Err(OpenError::Rejected(err)) => {
+                let _ = connection_channel.send(ConnectionEvent::Rejected(err));
+                return;
+            }
+            Err(OpenError::Unavailable) => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        backoff = MIN_BACKOFF;
+        let _ = connection_channel.send(ConnectionEvent::Connected(write_port));
+        read_frames(
+            read_port,
+            &diagnostic_channel,
+            &configuration_channel,
+            &packet_channel,
+            &frame_channel,
+            &mut log_file,
+        );
+        let _ = connection_channel.send(ConnectionEvent::Disconnected);
+    }
+}
+
+/// Turns diagnostic frames into text. A multi-byte character split between
+/// two frames is held back until the rest of it arrives, instead of turning
+/// into two replacement characters.
+struct Utf8Decoder {
+    incomplete: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    fn new() -> Self {
+        Self { incomplete: vec![] }
+    }
+
+    fn decode(&mut self, data: &[u8]) -> String {
+        let mut bytes = std::mem::take(&mut self.incomplete);
+        bytes.extend_from_slice(data);
+        // A UTF-8 character is at most 4 bytes, so the cut one starts in the last 3
+        let cut = (bytes.len().saturating_sub(3)..bytes.len()).find(|&start| {
+            std::str::from_utf8(&bytes[start..])
+                .is_err_and(|err| err.valid_up_to() == 0 && err.error_len().is_none())
+        });
+        if let Some(cut) = cut {
+            self.incomplete = bytes.split_off(cut);
+        }
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+}
+
+/// True if the frame looks like an IPv4 or IPv6 packet, by its version and
+/// a length field that matches the frame
+fn is_ip_packet(frame: &[u8]) -> bool {
+    let length = |offset: usize| {
+        frame
+            .get(offset..offset + 2)
+            .map(|bytes| usize::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+    };
+    match frame.first().map(|byte| byte >> 4) {
+        Some(4) => length(2) == Some(frame.len()),
+        Some(6) => frame.len() >= 40 && length(4) == Some(frame.len() - 40),
+        _ => false,
+    }
+}
+
+/// Decodes frames until reading from the port fails
+fn read_frames(
+    mut read_port: ReadPort,
+    diagnostic_channel: &Sender<String>,
+    configuration_channel: &Sender<Vec<u8>>,
+    packet_channel: &Sender<Vec<u8>>,
+    frame_channel: &Sender<FrameInfo>,
+    log_file: &mut Option<LogFile>,
 ) {
     let mut slip_decoder = Decoder::new();
+    let mut text_decoder = Utf8Decoder::new();
     let mut output = [0; 2024];
     let mut index = 0;
     let _ = slip_decoder.decode(&[0xc0], &mut output);
@@ -46,10 +289,15 @@ pub fn read_thread(
         let res = read_port.read(&mut buffer);
         let num = {
             match res {
+                // A socket was closed by the other side
+                Ok(0) => return,
                 Ok(num) => num,
-                Err(_) => {
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => {
                     continue;
                 }
+                Err(_) => {
+                    return;
+                }
             }
         };
         while offset < num {
@@ -57,25 +305,60 @@ pub fn read_thread(
                 match slip_decoder.decode(&buffer[offset..num], &mut output[index..]) {
                     Ok((used, out, end)) => (used, out, end),
                     Err(_) => {
-                        break;
+                        let _ = frame_channel.send(FrameInfo::Error);
+                        // Everything up to the next END is not slipmux, which
+                        // starts over there with a fresh decoder
+                        let rest = &buffer[offset..num];
+                        let skip = rest.iter().position(|&byte| byte == 0xc0);
+                        let skip = skip.unwrap_or(rest.len());
+                        let mut raw = output[..index].to_vec();
+                        raw.extend_from_slice(&rest[..skip]);
+                        let _ = frame_channel.send(FrameInfo::Raw(raw));
+                        slip_decoder = Decoder::new();
+                        output = [0; 2024];
+                        index = 0;
+                        offset += skip;
+                        continue;
                     }
                 }
             };
             index += out.len();
             offset += used;
 
-            if end {
+            // No frame is that long, but text without any END can be
+            if !end && index == output.len() {
+                let _ = frame_channel.send(FrameInfo::Raw(output.to_vec()));
+                index = 0;
+                continue;
+            }
+
+            // Senders may put an END in front of every frame, the empty frame is no frame
+            if end && index > 0 {
+                // Plain text between frames looks like a frame to the decoder
+                let known = matches!(output[0], DIAGNOSTIC | CONFIGURATION)
+                    || is_ip_packet(&output[0..index]);
+                let _ = frame_channel.send(if known {
+                    FrameInfo::Frame(output[0], index)
+                } else {
+                    FrameInfo::Raw(output[0..index].to_vec())
+                });
                 match output[0] {
                     DIAGNOSTIC => {
-                        let _ = diagnostic_channel
-                            .send(String::from_utf8_lossy(&output[1..index]).to_string());
+                        let warning = log_file
+                            .as_mut()
+                            .and_then(|log_file| log_file.write(&output[1..index]));
+                        if let Some(warning) = warning {
+                            let _ = diagnostic_channel.send(format!("\n{warning}\n"));
+                        }
+                        let _ = diagnostic_channel.send(text_decoder.decode(&output[1..index]));
                     }
                     CONFIGURATION => {
                         let _ = configuration_channel.send(output[1..index].to_vec());
                     }
-                    _ => {
+                    _ if known => {
                         let _ = packet_channel.send(output[0..index].to_vec());
                     }
+                    _ => {}
                 }
                 slip_decoder = Decoder::new();
                 let _ = slip_decoder.decode(&[0xc0], &mut output);
@@ -85,3 +368,164 @@ pub fn read_thread(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configuration_frame_fits_a_fully_escaped_payload() {
+        let mut packet = Packet::new();
+        packet.payload = vec![0xc0; 2000];
+        let frame = send_configuration(&packet);
+        let mut output = [0; 4096];
+        let (used, decoded, end) = Decoder::new().decode(&frame, &mut output).unwrap();
+        assert!(end);
+        assert_eq!(used, frame.len());
+        assert_eq!(decoded[0], CONFIGURATION);
+        assert_eq!(&decoded[1..], packet.to_bytes_unlimited().unwrap());
+    }
+
+    /// Decodes consecutive frames, as the device would read them
+    fn decode_frames(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = vec![];
+        let mut rest = data;
+        while !rest.is_empty() {
+            let mut output = [0; 512];
+            let mut decoder = Decoder::new();
+            let (used, frame, end) = decoder.decode(rest, &mut output).unwrap();
+            assert!(end);
+            frames.push(frame.to_vec());
+            rest = &rest[used..];
+        }
+        frames
+    }
+
+    #[test]
+    fn long_diagnostic_text_is_split_into_frames() {
+        // 400 bytes, chunks end in the middle of the two byte characters
+        let text = "ö".repeat(200);
+        let frames = send_diagnostic(&text);
+        assert_eq!(frames.len(), 4);
+
+        let mut received = vec![];
+        for frame in &frames {
+            let decoded = decode_frames(frame);
+            assert_eq!(decoded.len(), 1);
+            assert_eq!(decoded[0][0], DIAGNOSTIC);
+            received.extend_from_slice(&decoded[0][1..]);
+        }
+        assert_eq!(received, text.as_bytes());
+    }
+
+    /// Runs the read thread's decoding on `data` and collects what it sends
+    fn read(data: Vec<u8>) -> (Vec<String>, Vec<FrameInfo>) {
+        let (diagnostic_tx, diagnostic_rx) = std::sync::mpsc::channel();
+        let (configuration_tx, _configuration_rx) = std::sync::mpsc::channel();
+        let (packet_tx, _packet_rx) = std::sync::mpsc::channel();
+        let (frame_tx, frame_rx) = std::sync::mpsc::channel();
+        read_frames(
+            Box::new(io::Cursor::new(data)),
+            &diagnostic_tx,
+            &configuration_tx,
+            &packet_tx,
+            &frame_tx,
+            &mut None,
+        );
+        (
+            diagnostic_rx.try_iter().collect(),
+            frame_rx.try_iter().collect(),
+        )
+    }
+
+    #[test]
+    fn text_before_the_first_frame_is_raw() {
+        let mut data = b"main(): This is RIOT!\r\n".to_vec();
+        data.extend(&send_diagnostic("hi")[0]);
+        let (diagnostic, frames) = read(data);
+        assert_eq!(diagnostic, vec!["hi"]);
+        assert!(
+            matches!(&frames[0], FrameInfo::Raw(bytes) if bytes == b"main(): This is RIOT!\r\n")
+        );
+        assert!(matches!(frames[1], FrameInfo::Frame(DIAGNOSTIC, 3)));
+    }
+
+    #[test]
+    fn decoder_starts_over_after_an_error() {
+        // An escape followed by anything but ESC_END or ESC_ESC is invalid
+        let mut data = vec![0xc0, 0x0a, b'a', 0xdb, b'x', b'y'];
+        data.extend(&send_diagnostic("ok")[0]);
+        let (diagnostic, frames) = read(data);
+        assert!(matches!(frames[0], FrameInfo::Error));
+        assert!(
+            matches!(&frames[1], FrameInfo::Raw(bytes) if bytes == &[0x0a, b'a', 0xdb, b'x', b'y'])
+        );
+        assert!(matches!(frames[2], FrameInfo::Frame(DIAGNOSTIC, 3)));
+        assert_eq!(diagnostic, vec!["ok"]);
+    }
+
+    #[test]
+    fn long_text_without_end_is_raw() {
+        let (_, frames) = read(vec![b'x'; 5000]);
+        let raw: usize = frames
+            .iter()
+            .map(|frame| match frame {
+                FrameInfo::Raw(bytes) => bytes.len(),
+                _ => 0,
+            })
+            .sum();
+        // The rest is still waiting for an END
+        assert_eq!(raw, 4048);
+    }
+
+    #[test]
+    fn ip_packets_are_recognized_by_their_length() {
+        let mut ipv6 = vec![0x60, 0, 0, 0, 0, 2];
+        ipv6.resize(42, 0);
+        assert!(is_ip_packet(&ipv6));
+        ipv6.push(0);
+        assert!(!is_ip_packet(&ipv6));
+
+        let mut ipv4 = vec![0x45, 0, 0, 20];
+        ipv4.resize(20, 0);
+        assert!(is_ip_packet(&ipv4));
+        assert!(!is_ip_packet(b"main(): This is RIOT!"));
+    }
+
+    #[test]
+    fn characters_split_across_frames_are_reassembled() {
+        for text in ["aö", "a日b", "🪼!"] {
+            let bytes = text.as_bytes();
+            for cut in 0..=bytes.len() {
+                let mut decoder = Utf8Decoder::new();
+                let mut decoded = decoder.decode(&bytes[..cut]);
+                decoded.push_str(&decoder.decode(&bytes[cut..]));
+                assert_eq!(decoded, text, "cut after {cut} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn character_split_over_three_frames_is_reassembled() {
+        let mut decoder = Utf8Decoder::new();
+        let mut decoded = String::new();
+        for byte in "🪼".as_bytes() {
+            decoded.push_str(&decoder.decode(&[*byte]));
+        }
+        assert_eq!(decoded, "🪼");
+    }
+
+    #[test]
+    fn invalid_bytes_are_not_held_back() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"a\xffb"), "a\u{fffd}b");
+        assert_eq!(decoder.decode(b"c"), "c");
+    }
+
+    #[test]
+    fn short_diagnostic_text_is_one_frame() {
+        let frames = send_diagnostic("help\n");
+        assert_eq!(frames.len(), 1);
+        assert_eq!(decode_frames(&frames[0]), vec![b"\x0ahelp\n".to_vec()]);
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+/// Running totals of the traffic with the device, shown in an overlay
+pub struct Statistics {
+    diagnostic_sent: usize,
+    diagnostic_received: usize,
+    requests: usize,
+    responses: usize,
+    total_latency: Duration,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Self {
+            diagnostic_sent: 0,
+            diagnostic_received: 0,
+            requests: 0,
+            responses: 0,
+            total_latency: Duration::ZERO,
+        }
+    }
+
+    pub fn add_diagnostic_sent(&mut self, bytes: usize) {
+        self.diagnostic_sent += bytes;
+    }
+
+    pub fn add_diagnostic_received(&mut self, bytes: usize) {
+        self.diagnostic_received += bytes;
+    }
+
+    pub fn add_request(&mut self) {
+        self.requests += 1;
+    }
+
+    /// A request got its complete response after `latency`
+    pub fn add_response(&mut self, latency: Duration) {
+        self.responses += 1;
+        self.total_latency += latency;
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let average = match u32::try_from(self.responses) {
+            Ok(responses) if responses > 0 => {
+                format!("{} ms", (self.total_latency / responses).as_millis())
+            }
+            _ => "-".to_string(),
+        };
+        vec![
+            format!("Diagnostic bytes sent:     {}", self.diagnostic_sent),
+            format!("Diagnostic bytes received: {}", self.diagnostic_received),
+            format!("CoAP requests sent:        {}", self.requests),
+            format!("CoAP responses received:   {}", self.responses),
+            format!("Average response latency:  {average}"),
+        ]
+    }
+}
@@ -0,0 +1,53 @@
+use clap::ValueEnum;
+use ratatui::style::Color;
+use ratatui::style::Style;
+
+/// Named palettes for `--theme`
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ThemeName {
+    /// For dark terminal backgrounds
+    Dark,
+    /// For white or bright terminal backgrounds
+    Light,
+    /// The Solarized accent colors, for either background
+    Solarized,
+}
+
+/// Colors of everything that is highlighted
+#[derive(Clone, Copy)]
+pub struct Theme {
+    /// Timeouts and malformed frames
+    pub error: Style,
+    /// Link health warnings in the footer
+    pub warning: Style,
+    /// Resource paths in link-format payloads and the resource tree
+    pub path: Style,
+    /// Attribute names in link-format payloads
+    pub attribute: Style,
+}
+
+impl ThemeName {
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Dark => Theme {
+                error: Style::new().fg(Color::Red),
+                warning: Style::new().fg(Color::Yellow),
+                path: Style::new().fg(Color::Cyan),
+                attribute: Style::new().fg(Color::Yellow),
+            },
+            // Yellow and cyan are hard to read on white
+            Self::Light => Theme {
+                error: Style::new().fg(Color::Red),
+                warning: Style::new().fg(Color::Magenta),
+                path: Style::new().fg(Color::Blue),
+                attribute: Style::new().fg(Color::Magenta),
+            },
+            Self::Solarized => Theme {
+                error: Style::new().fg(Color::Rgb(0xdc, 0x32, 0x2f)),
+                warning: Style::new().fg(Color::Rgb(0xcb, 0x4b, 0x16)),
+                path: Style::new().fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+                attribute: Style::new().fg(Color::Rgb(0xb5, 0x89, 0x00)),
+            },
+        }
+    }
+}
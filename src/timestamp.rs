@@ -0,0 +1,79 @@
+use std::time::SystemTime;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::Utc;
+
+/// How points in time are rendered, configured via the command line
+pub struct TimestampFormat {
+    format: String,
+    local: bool,
+}
+
+impl TimestampFormat {
+    pub fn new(format: &str, local: bool) -> Self {
+        Self {
+            format: format.to_string(),
+            local,
+        }
+    }
+
+    pub fn format(&self, time: SystemTime) -> String {
+        if self.local {
+            DateTime::<Local>::from(time)
+                .format(&self.format)
+                .to_string()
+        } else {
+            DateTime::<Utc>::from(time).format(&self.format).to_string()
+        }
+    }
+}
+
+/// Rejects strftime strings chrono can not render, formatting them later would panic
+pub fn parse_format(format: &str) -> Result<String, String> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("invalid timestamp format '{format}'"));
+    }
+    Ok(format.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// 2024-03-01 12:34:56.789 UTC
+    fn time() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(1_709_296_496_789)
+    }
+
+    #[test]
+    fn default_format_is_rfc3339_in_utc() {
+        let timestamps = TimestampFormat::new("%Y-%m-%dT%H:%M:%S%.3f%:z", false);
+        assert_eq!(timestamps.format(time()), "2024-03-01T12:34:56.789+00:00");
+    }
+
+    #[test]
+    fn custom_format_changes_the_output() {
+        let timestamps = TimestampFormat::new("%H:%M:%S%.3f", false);
+        assert_eq!(timestamps.format(time()), "12:34:56.789");
+        let timestamps = TimestampFormat::new("%d.%m.%Y", false);
+        assert_eq!(timestamps.format(time()), "01.03.2024");
+    }
+
+    #[test]
+    fn local_time_carries_the_local_offset() {
+        let timestamps = TimestampFormat::new("%:z", true);
+        let offset = DateTime::<Local>::from(time()).format("%:z").to_string();
+        assert_eq!(timestamps.format(time()), offset);
+    }
+
+    #[test]
+    fn invalid_format_is_rejected() {
+        assert!(parse_format("%H:%M").is_ok());
+        assert!(parse_format("%Q").is_err());
+    }
+}
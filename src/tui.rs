@@ -1,49 +1,99 @@
-use crate::tui::Constraint::Fill;
-use crate::tui::Constraint::Length;
-use crate::tui::Constraint::Max;
 use crate::tui::Constraint::Min;
-use coap_lite::CoapResponse;
 use core::iter::zip;
+use ratatui::prelude::Position;
 use ratatui::prelude::Rect;
-use ratatui::prelude::StatefulWidget;
 use ratatui::prelude::Widget;
 use ratatui::widgets::Borders;
-use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt::Write;
-use std::sync::mpsc;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
 use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 use tui_scrollview::{ScrollView, ScrollViewState};
 
+use cbor_edn::Item;
+use cbor_edn::StandaloneItem;
+use coap_lite::block_handler::BlockValue;
 use coap_lite::CoapOption;
 use coap_lite::CoapRequest;
 use coap_lite::ContentFormat;
 use coap_lite::MessageClass;
+use coap_lite::MessageType;
 use coap_lite::Packet;
 use coap_lite::RequestType as Method;
+use coap_lite::ResponseType;
 use crossterm::event::Event;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyModifiers;
+use crossterm::event::MouseButton;
+use crossterm::event::MouseEvent;
+use crossterm::event::MouseEventKind;
 use ratatui::backend::Backend;
 use ratatui::backend::CrosstermBackend;
+use ratatui::buffer::Buffer;
 use ratatui::layout::Alignment;
 use ratatui::layout::Constraint;
 use ratatui::layout::Direction;
 use ratatui::layout::Layout;
 use ratatui::layout::Size;
+use ratatui::style::Color;
+use ratatui::style::Style;
 use ratatui::text::Line;
 use ratatui::text::Span;
 use ratatui::text::Text;
 use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
 use ratatui::widgets::Paragraph;
 use ratatui::Frame;
 use ratatui::Terminal;
-use serialport::SerialPort;
 
+use crate::aliases::Aliases;
+use crate::input::classify_input;
+use crate::input::next_grapheme_boundary;
+use crate::input::next_word_boundary;
+use crate::input::previous_grapheme_boundary;
+use crate::input::previous_word_boundary;
+use crate::input::DiagnosticFilter;
+use crate::input::InputType;
+use crate::input::ReverseSearch;
+use crate::keymap::Action;
+use crate::keymap::KeyMap;
+use crate::link_format::parse_link_format;
+use crate::link_format::resource_tree;
+use crate::link_format::LinkEntry;
+use crate::link_format::TreeRow;
+use crate::link_health::LinkHealth;
+use crate::ping::Ping;
+use crate::ps::fmt_ps_table;
+use crate::ps::parse_ps;
+use crate::ps::PsSort;
+use crate::replay::Replay;
+use crate::replay::ReplayEntry;
+use crate::request::set_target;
+use crate::request::Request;
+use crate::scan::Scan;
+use crate::shell_output::collapse_repeats;
+use crate::shell_output::DiagnosticOutput;
+use crate::slipmux::channel_name;
+use crate::slipmux::encoded_frame_info;
 use crate::slipmux::send_configuration;
+use crate::slipmux::send_configuration_bytes;
 use crate::slipmux::send_diagnostic;
+use crate::slipmux::ConnectionEvent;
+use crate::slipmux::FrameInfo;
+use crate::slipmux::WritePort;
+use crate::statistics::Statistics;
+use crate::theme::Theme;
+use crate::timestamp::TimestampFormat;
+use crate::Cli;
 
 enum Refresh {
     /// Update the TUI
@@ -54,6 +104,160 @@ enum Refresh {
     Quit,
 }
 
+/// Vertical scroll position of a pane
+struct PaneScroll {
+    /// Line offset from the top
+    position: usize,
+    /// Stick to the bottom so that new content is always visible
+    follow: bool,
+    /// Lines scrolled since the last frame, applied at once when drawing
+    pending: isize,
+    /// Content arrived while scrolled away from the bottom
+    unseen: bool,
+}
+
+impl PaneScroll {
+    fn new() -> Self {
+        Self {
+            position: 0,
+            follow: true,
+            pending: 0,
+            unseen: false,
+        }
+    }
+
+    /// Lines were removed from the start, a position above the bottom has to
+    /// move up with them so the same content stays in view
+    fn remove_lines(&mut self, count: usize) {
+        if !self.follow {
+            self.position = self.position.saturating_sub(count);
+        }
+    }
+
+    /// Shows the line at `position` at the top, without following new content
+    fn scroll_to(&mut self, position: usize) {
+        self.pending = 0;
+        self.position = position;
+        self.follow = false;
+    }
+
+    /// Remembers new content the user can not see because they scrolled up
+    fn add_content(&mut self) {
+        if !self.follow {
+            self.unseen = true;
+        }
+    }
+
+    fn scroll(&mut self, lines: isize) {
+        self.pending += lines;
+    }
+
+    /// Pages are measured in lines of the visible area, not in entries,
+    /// as entries can span any number of lines
+    fn scroll_page_up(&mut self, viewport_height: usize) {
+        self.scroll(-(viewport_height as isize));
+    }
+
+    fn scroll_page_down(&mut self, viewport_height: usize) {
+        self.scroll(viewport_height as isize);
+    }
+
+    fn scroll_to_top(&mut self) {
+        self.pending = 0;
+        self.position = 0;
+        self.follow = false;
+    }
+
+    /// Follows new content again, the next frame snaps to the bottom
+    fn scroll_to_bottom(&mut self) {
+        self.pending = 0;
+        self.follow = true;
+    }
+
+    /// Applies the pending scrolling and returns the offset to render
+    /// a pane with, which can be scrolled at most `max_offset` lines
+    fn offset(&mut self, max_offset: usize) -> usize {
+        if self.follow {
+            self.position = max_offset;
+        }
+        self.position = self
+            .position
+            .saturating_add_signed(self.pending)
+            .min(max_offset);
+        self.pending = 0;
+        // Scrolling back to the bottom re-enables following
+        self.follow = self.position == max_offset;
+        if self.follow {
+            self.unseen = false;
+        }
+        self.position
+    }
+}
+
+/// Number of received frames kept for the slipmux frame view
+const FRAME_LOG_SIZE: usize = 1000;
+/// Latest bytes kept for the raw serial view
+const RAW_SERIAL_SIZE: usize = 4096;
+
+/// Neither side of the split gets narrower than this many percent
+const MIN_SPLIT: u16 = 20;
+const SPLIT_STEP: u16 = 5;
+
+/// Time to press Esc a second time to quit with requests in flight
+const QUIT_CONFIRM_TIME: Duration = Duration::from_secs(3);
+
+/// How many of the latest commands `RERUN` without an index lists
+const RERUN_LISTED: usize = 10;
+
+/// Resources queried to fill the info pane, along with the resource discovery
+const INFO_PATHS: [&str; 4] = [
+    "/riot/ver",
+    "/riot/board",
+    "/.well-known/ifconfig",
+    "/.well-known/core",
+];
+
+/// Entries of the CoAP log that are not requests
+enum LogNote {
+    /// A configuration frame that is not CoAP
+    Malformed(String),
+    /// Written by Jelly itself, e.g. the summary of a SCAN
+    Report { title: String, text: String },
+}
+
+/// Which frames the slipmux frame view shows
+#[derive(Clone, Copy)]
+enum FrameFilter {
+    Both,
+    Received,
+    Sent,
+}
+
+impl FrameFilter {
+    fn next(self) -> Self {
+        match self {
+            Self::Both => Self::Received,
+            Self::Received => Self::Sent,
+            Self::Sent => Self::Both,
+        }
+    }
+
+    fn shows(self, sent: bool) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Received => !sent,
+            Self::Sent => sent,
+        }
+    }
+}
+
+/// The pane that keyboard scrolling applies to
+#[derive(PartialEq)]
+enum ScrollTarget {
+    Diagnostic,
+    Configuration,
+}
+
 pub enum ElementInFocus {
     UserInput,
 }
@@ -64,34 +268,129 @@ pub struct App {
     version: String,
     board: String,
     token_count: u16,
+    message_id: u16,
     user_commands: Vec<String>,
     user_command: String,
+    /// Byte offset of the cursor within `user_command`
+    input_position: usize,
+    /// Text removed by the last delete to start, end or word, for yanking it back
+    killed: String,
     user_command_cursor: usize,
     autocomplete: Vec<String>,
-    diagnostic_messages: String,
-    configuration_requests: Vec<CoapRequest<String>>,
+    keymap: KeyMap,
+    aliases: Aliases,
+    reverse_search: Option<ReverseSearch>,
+    diagnostic_filter: Option<DiagnosticFilter>,
+    /// Search through the requests and responses, cycled with next/previous match
+    request_search: Option<DiagnosticFilter>,
+    /// Scroll the configuration pane to the selected request on the next frame
+    reveal_selection: bool,
+    diagnostic_messages: Vec<DiagnosticOutput>,
+    /// Anchor and cursor of the selected diagnostic lines, as shown in the pane
+    diagnostic_selection: Option<(usize, usize)>,
+    scrollback: usize,
+    ps_sort: PsSort,
+    /// Older diagnostic output was dropped to stay within `scrollback`
+    diagnostic_trimmed: bool,
+    decode_shell_output: bool,
+    compact_cbor: bool,
+    collapse_repeats: bool,
+    /// Show each shell command above its output
+    echo_commands: bool,
+    errors_only: bool,
+    /// Short feedback for the user, shown in the footer
+    status: String,
+    link_health: LinkHealth,
+    statistics: Statistics,
+    show_statistics: bool,
+    /// ETag and full response of the latest 2.05 Content per path, sent
+    /// along with the next GET so the device can answer 2.03 Valid instead
+    etags: HashMap<String, (Vec<u8>, Packet)>,
+    /// Scan of all resources in progress
+    scan: Option<Scan>,
+    /// Round-trip measurement in progress
+    ping: Option<Ping>,
+    /// Resources found in /.well-known/core
+    resources: Vec<LinkEntry>,
+    /// Selected row while the resource tree is shown
+    resource_tree: Option<usize>,
+    /// Width of the diagnostic side in percent of the screen
+    split_percent: u16,
+    screenshot_requested: bool,
+    configuration_requests: Vec<Request>,
+    /// Malformed frames and reports, with the number of requests sent
+    /// before them to place them in the log
+    log_notes: Vec<(usize, LogNote)>,
+    selected_request: Option<usize>,
+    coap_timeout: Duration,
+    /// Minimum time between two redraws, from `--max-fps`
+    frame_time: Duration,
+    force_quit: bool,
+    /// When quitting was refused because of requests in flight
+    quit_requested: Option<Instant>,
     configuration_packets: Vec<Packet>,
-    write_port: Box<dyn SerialPort>,
+    last_diagnostic: Option<Instant>,
+    last_configuration: Option<Instant>,
+    stale_after: Option<Duration>,
+    color: bool,
+    theme: Theme,
+    /// Status markers without emoji, for terminals that can not render them
+    ascii: bool,
+    /// Counts the draws to animate the marker of pending requests
+    spinner_tick: usize,
+    session_file: Option<PathBuf>,
+    timestamps: TimestampFormat,
+    /// Latest frames on the wire, flagged true if sent to the device
+    frame_log: VecDeque<(bool, String)>,
+    show_frame_log: bool,
+    frame_filter: FrameFilter,
+    /// Bytes outside of diagnostic and configuration frames, with `--raw-serial`
+    raw_serial: Option<VecDeque<u8>>,
+    show_raw_serial: bool,
+    /// Frames and bytes received from and sent to the device
+    frames_received: (usize, usize),
+    frames_sent: (usize, usize),
+    scroll_target: ScrollTarget,
+    diagnostic_scroll: PaneScroll,
+    diagnostic_area: Rect,
+    configuration_scroll: PaneScroll,
+    configuration_area: Rect,
+    write_port: Option<WritePort>,
+    /// Name of the serial device or socket for the footer
+    device: String,
+    connection_error: Option<String>,
+    replay: Option<Replay>,
+    connection_channel: Receiver<ConnectionEvent>,
     diagnostic_channel: Receiver<String>,
     configuration_channel: Receiver<Vec<u8>>,
     packet_channel: Receiver<Vec<u8>>,
+    frame_channel: Receiver<FrameInfo>,
 }
 
 impl App {
     fn new(
-        write_port: Box<dyn SerialPort>,
+        cli: &Cli,
+        replay: Option<Replay>,
+        connection_channel: Receiver<ConnectionEvent>,
         diagnostic_channel: Receiver<String>,
         configuration_channel: Receiver<Vec<u8>>,
         packet_channel: Receiver<Vec<u8>>,
+        frame_channel: Receiver<FrameInfo>,
     ) -> Self {
+        let (keymap, mut warnings) = KeyMap::load(cli.keymap.as_deref());
+        let (aliases, alias_warnings) = Aliases::load(cli.aliases.as_deref());
+        warnings.extend(alias_warnings);
         Self {
             focus: ElementInFocus::UserInput,
             ip: String::new(),
             version: String::new(),
             board: String::new(),
             token_count: 0,
+            message_id: 0,
             user_commands: vec![],
             user_command: String::new(),
+            input_position: 0,
+            killed: String::new(),
             user_command_cursor: 0,
             autocomplete: vec![
                 "help".to_string(),
@@ -120,197 +419,1439 @@ impl App {
                 "txtsnd".to_string(),
                 "version".to_string(),
             ],
-            diagnostic_messages: String::new(),
+            reverse_search: None,
+            keymap,
+            aliases,
+            diagnostic_filter: None,
+            request_search: None,
+            reveal_selection: false,
+            diagnostic_messages: vec![DiagnosticOutput::new(None)],
+            diagnostic_selection: None,
+            scrollback: cli.scrollback,
+            ps_sort: cli.ps_sort,
+            diagnostic_trimmed: false,
+            decode_shell_output: true,
+            compact_cbor: false,
+            collapse_repeats: true,
+            echo_commands: true,
+            errors_only: false,
+            status: warnings.join("; "),
+            link_health: LinkHealth::new(),
+            statistics: Statistics::new(),
+            show_statistics: false,
+            resources: vec![],
+            scan: None,
+            ping: None,
+            etags: HashMap::new(),
+            resource_tree: None,
+            split_percent: 60,
+            screenshot_requested: false,
             configuration_requests: vec![],
+            log_notes: vec![],
+            selected_request: None,
+            coap_timeout: Duration::from_secs(cli.coap_timeout),
+            frame_time: Duration::from_secs(1) / cli.max_fps,
+            force_quit: cli.force_quit,
+            quit_requested: None,
             configuration_packets: vec![],
-            write_port,
+            last_diagnostic: None,
+            last_configuration: None,
+            stale_after: (cli.stale_after > 0).then(|| Duration::from_secs(cli.stale_after)),
+            color: cli.use_color(),
+            theme: cli.theme.theme(),
+            ascii: cli.use_ascii(),
+            spinner_tick: 0,
+            session_file: cli.save.clone(),
+            timestamps: TimestampFormat::new(&cli.timestamp_format, cli.local_time),
+            frame_log: VecDeque::new(),
+            show_frame_log: false,
+            frame_filter: FrameFilter::Both,
+            raw_serial: cli.raw_serial.then(VecDeque::new),
+            show_raw_serial: false,
+            frames_received: (0, 0),
+            frames_sent: (0, 0),
+            scroll_target: ScrollTarget::Diagnostic,
+            diagnostic_scroll: PaneScroll::new(),
+            diagnostic_area: Rect::default(),
+            configuration_scroll: PaneScroll::new(),
+            configuration_area: Rect::default(),
+            write_port: None,
+            device: cli.endpoint().to_string(),
+            connection_error: None,
+            replay,
+            connection_channel,
             diagnostic_channel,
             configuration_channel,
             packet_channel,
+            frame_channel,
         }
     }
 
+    /// Every confirmable message needs its own id so the device can tell
+    /// retransmissions from new messages
+    fn get_new_message_id(&mut self) -> u16 {
+        self.message_id = self.message_id.wrapping_add(1);
+        self.message_id
+    }
+
     fn get_new_token(&mut self) -> Vec<u8> {
         self.token_count += 1;
         self.token_count.to_le_bytes().to_vec()
     }
 
-    fn poll_ifconfig(&mut self) {
-        {
+    /// Sends a request with a fresh token and keeps it around to match its response
+    fn send_request(&mut self, mut request: CoapRequest<String>) {
+        request.message.set_token(self.get_new_token());
+        request.message.header.message_id = self.get_new_message_id();
+        request.message.add_option(CoapOption::Block2, vec![0x05]);
+        if request.get_method() == &Method::Get {
+            if let Some((etag, _)) = self.etags.get(&request_target(&request.message)) {
+                request.message.add_option(CoapOption::ETag, etag.clone());
+            }
+        }
+        let request = Request::new(request);
+        self.write_packet(&request.coap.message);
+        self.statistics.add_request();
+        self.configuration_requests.push(request);
+    }
+
+    /// Queues a GET for every discovered resource, returns false if there are none
+    fn start_scan(&mut self) -> bool {
+        if self.scan.is_some() {
+            self.status = "A scan is already running".to_string();
+            return false;
+        }
+        let paths: Vec<String> = self
+            .resources
+            .iter()
+            .map(|link| link.path.clone())
+            .filter(|path| path.starts_with('/'))
+            .collect();
+        if paths.is_empty() {
+            self.status = "No resources discovered yet, F8 asks the device".to_string();
+            return false;
+        }
+        self.status = format!("Scanning {} resources", paths.len());
+        self.scan = Some(Scan::new(paths));
+        true
+    }
+
+    /// Sends the next requests of a running scan and adds its summary to the
+    /// CoAP log once all are answered. Returns true if anything changed.
+    fn advance_scan(&mut self) -> bool {
+        let Some(mut scan) = self.scan.take() else {
+            return false;
+        };
+        let mut changed = false;
+        while let Some(path) = scan.next_path(&self.configuration_requests) {
+            scan.add_request(self.configuration_requests.len());
             let mut request: CoapRequest<String> = CoapRequest::new();
             request.set_method(Method::Get);
-            request.set_path("/riot/ver");
-            request.message.add_option(CoapOption::Block2, vec![0x05]);
-            let (data, size) = send_configuration(&request.message);
-            let _ = self.write_port.write(&data[..size]);
-
-            let mut version = String::new();
-            match self.configuration_channel.recv() {
-                Ok(data) => {
-                    let response = Packet::from_bytes(&data);
-                    if response.is_ok() {
-                        _ = write!(
-                            version,
-                            "{}",
-                            String::from_utf8_lossy(&response.unwrap().payload)
-                        );
-                    } else {
-                        _ = write!(
-                            version,
-                            "{}",
-                            String::from_utf8_lossy(b"Failed to parse /riot/ver packet")
-                        );
+            set_target(&mut request, &path);
+            self.send_request(request);
+            changed = true;
+        }
+        if !scan.is_done(&self.configuration_requests) {
+            self.scan = Some(scan);
+            return changed;
+        }
+        self.log_notes.push((
+            self.configuration_requests.len(),
+            LogNote::Report {
+                title: "SCAN".to_string(),
+                text: scan.fmt_summary(&self.configuration_requests),
+            },
+        ));
+        self.configuration_scroll.add_content();
+        self.status = "Scan complete".to_string();
+        true
+    }
+
+    /// Sends the next request of a running ping when it is due and shows the
+    /// statistics so far, which end up in the CoAP log once all are
+    /// answered. Returns true if anything changed.
+    fn advance_ping(&mut self) -> bool {
+        let Some(mut ping) = self.ping.take() else {
+            return false;
+        };
+        if ping.is_due() {
+            ping.add_request(self.configuration_requests.len());
+            let mut request: CoapRequest<String> = CoapRequest::new();
+            request.set_method(Method::Get);
+            set_target(&mut request, &ping.path);
+            self.send_request(request);
+        }
+        let progress = ping.fmt_progress(&self.configuration_requests);
+        let changed = progress != self.status;
+        if !ping.is_done(&self.configuration_requests) {
+            self.status = progress;
+            self.ping = Some(ping);
+            return changed;
+        }
+        self.log_notes.push((
+            self.configuration_requests.len(),
+            LogNote::Report {
+                title: "PING".to_string(),
+                text: ping.fmt_summary(&self.configuration_requests),
+            },
+        ));
+        self.configuration_scroll.add_content();
+        self.status = "Ping complete".to_string();
+        true
+    }
+
+    /// Lists the most recent commands in the status line, or puts the one
+    /// with the given index back into the input and submits it
+    fn rerun(&mut self, index: Option<usize>) -> Refresh {
+        let Some(index) = index else {
+            let start = self.user_commands.len().saturating_sub(RERUN_LISTED);
+            let listed: Vec<String> = self.user_commands[start..]
+                .iter()
+                .enumerate()
+                .map(|(offset, command)| format!("{}: {}", start + offset + 1, command.trim()))
+                .collect();
+            self.status = if listed.is_empty() {
+                "No commands to rerun yet".to_string()
+            } else {
+                listed.join(" | ")
+            };
+            self.set_user_command(String::new());
+            return Refresh::Update;
+        };
+        let Some(command) = index
+            .checked_sub(1)
+            .and_then(|index| self.user_commands.get(index))
+        else {
+            self.status = format!("There is no command {index}");
+            return Refresh::Update;
+        };
+        if self.write_port.is_none() {
+            self.status = "Not connected, nothing to rerun the command on".to_string();
+            return Refresh::Update;
+        }
+        self.set_user_command(command.clone());
+        self.on_action(Action::Submit)
+    }
+
+    /// Deregisters the newest active observation of `path` with Observe 1 and
+    /// the original token. Returns false if there is none.
+    fn cancel_observation(&mut self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        let Some(index) = self
+            .configuration_requests
+            .iter()
+            .rposition(|req| req.observing && request_target(&req.coap.message) == path)
+        else {
+            return false;
+        };
+        let message_id = self.get_new_message_id();
+        let request = &mut self.configuration_requests[index];
+        request.observing = false;
+        let mut packet = request.coap.message.clone();
+        packet.set_observe_value(1);
+        packet.header.message_id = message_id;
+        request.track(packet.clone());
+        self.write_packet(&packet);
+        true
+    }
+
+    /// Sends bytes without encoding them, they are tracked like any other
+    /// request if they happen to form a valid CoAP packet
+    fn send_raw(&mut self, bytes: Vec<u8>) {
+        let data = send_configuration_bytes(&bytes);
+        self.write_to_device(&data);
+        match Packet::from_bytes(&bytes) {
+            Ok(packet) => {
+                self.configuration_packets.push(packet.clone());
+                self.statistics.add_request();
+                self.configuration_requests
+                    .push(Request::new(CoapRequest::from_packet(
+                        packet,
+                        String::new(),
+                    )));
+                self.status = format!("Sent raw frame, {} bytes", bytes.len());
+            }
+            Err(_) => {
+                self.status = format!("Sent raw frame (unparsed), {} bytes", bytes.len());
+            }
+        }
+    }
+
+    fn write_packet(&mut self, packet: &Packet) {
+        let data = send_configuration(packet);
+        self.configuration_packets.push(packet.clone());
+        self.write_to_device(&data);
+    }
+
+    fn on_configuration_msg(&mut self, data: &[u8]) {
+        // A flaky serial line can corrupt frames, that must not take down the UI
+        let response = match Packet::from_bytes(data) {
+            Ok(response) => response,
+            Err(err) => {
+                self.log_notes.push((
+                    self.configuration_requests.len(),
+                    LogNote::Malformed(format!(
+                        "malformed CoAP frame ({} bytes): {err}",
+                        data.len()
+                    )),
+                ));
+                return;
+            }
+        };
+        if response.header.get_type() == MessageType::Reset {
+            self.link_health.add_failure();
+        }
+        let token = response.get_token();
+        let mut follow_ups = vec![];
+        let mut completed = vec![];
+        for (index, request) in self.configuration_requests.iter_mut().enumerate() {
+            request.on_acknowledgement(&response);
+            if request.coap.message.get_token() == token {
+                let pending = request.coap.response.is_none();
+                for next in request.on_response(response.clone()) {
+                    follow_ups.push((index, next));
+                }
+                if let (true, Some(received_at)) = (pending, request.received_at) {
+                    let latency = received_at.duration_since(request.sent_at);
+                    self.statistics.add_response(latency.unwrap_or_default());
+                }
+                let Some(done) = &request.coap.response else {
+                    continue;
+                };
+                let path = request_target(&request.coap.message);
+                let etag = done.message.get_first_option(CoapOption::ETag);
+                match done.message.header.code {
+                    MessageClass::Response(ResponseType::Content) => {
+                        if let Some(etag) = etag {
+                            self.etags
+                                .insert(path.clone(), (etag.clone(), done.message.clone()));
+                        }
+                        completed.push((path, done.message.payload.clone()));
                     }
+                    MessageClass::Response(ResponseType::Valid) => {
+                        if let Some((_, earlier)) = self
+                            .etags
+                            .get(&path)
+                            .filter(|(cached, _)| Some(cached) == etag)
+                        {
+                            request.reuse_payload(earlier);
+                        }
+                    }
+                    _ => {}
                 }
-                Err(_) => panic!(),
             }
-            let version = version
-                .split_once('(')
-                .unwrap()
-                .1
-                .split_once(')')
-                .unwrap()
-                .0;
-            _ = write!(self.version, "{}", version);
         }
+        for (path, payload) in completed {
+            self.on_info_response(&path, &payload);
+        }
+        self.configuration_packets.push(response);
+        for (index, mut packet) in follow_ups {
+            // ACKs and resets for the device reuse its message id
+            if matches!(
+                packet.header.get_type(),
+                MessageType::Confirmable | MessageType::NonConfirmable
+            ) {
+                packet.header.message_id = self.get_new_message_id();
+                self.configuration_requests[index].track(packet.clone());
+            }
+            self.write_packet(&packet);
+        }
+    }
+
+    /// Requests still waiting for an answer and recent resets or timeouts
+    fn link_summary(&self) -> String {
+        format!(
+            "{} in flight, {} RST/timeouts in the last minute",
+            self.requests_in_flight(),
+            self.link_health.recent_failures()
+        )
+    }
+
+    fn requests_in_flight(&self) -> usize {
+        self.configuration_requests
+            .iter()
+            .filter(|req| req.coap.response.is_none() && !req.timed_out)
+            .count()
+    }
+
+    /// Refuses the first quit while requests still await a response, their
+    /// answers would be lost. A second quit within `QUIT_CONFIRM_TIME` goes through.
+    fn confirm_quit(&mut self) -> bool {
+        let in_flight = self.requests_in_flight();
+        if self.force_quit
+            || in_flight == 0
+            || self
+                .quit_requested
+                .is_some_and(|requested| requested.elapsed() < QUIT_CONFIRM_TIME)
         {
-            let mut request: CoapRequest<String> = CoapRequest::new();
-            request.set_method(Method::Get);
-            request.set_path("/riot/board");
-            request.message.add_option(CoapOption::Block2, vec![0x05]);
-            let (data, size) = send_configuration(&request.message);
-            let _ = self.write_port.write(&data[..size]);
-
-            match self.configuration_channel.recv() {
-                Ok(data) => {
-                    let response = Packet::from_bytes(&data);
-                    if response.is_ok() {
-                        _ = write!(
-                            self.board,
-                            "{:}",
-                            String::from_utf8_lossy(&response.unwrap().payload)
-                        );
-                    } else {
-                        _ = write!(self.board, "Failed to parse /riot/board packet");
+            return true;
+        }
+        self.quit_requested = Some(Instant::now());
+        self.status = format!("{in_flight} requests in flight, press Esc or Ctrl+C again to quit");
+        false
+    }
+
+    /// Applies a recorded event as if it just happened
+    fn on_replay_entry(&mut self, entry: ReplayEntry) {
+        match entry {
+            ReplayEntry::Diagnostic { command, text } => {
+                let mut output = DiagnosticOutput::new(command.as_deref());
+                output.push_str(&text);
+                self.diagnostic_messages.push(output);
+                self.trim_scrollback();
+                self.last_diagnostic = Some(Instant::now());
+                self.diagnostic_scroll.add_content();
+            }
+            ReplayEntry::Request(frame) => {
+                if let Ok(packet) = Packet::from_bytes(&frame) {
+                    self.configuration_requests
+                        .push(Request::new(CoapRequest::from_packet(
+                            packet,
+                            String::new(),
+                        )));
+                }
+            }
+            ReplayEntry::Response(frame) => {
+                let Ok(packet) = Packet::from_bytes(&frame) else {
+                    return;
+                };
+                // Saved responses are already reassembled, skip the Block2 handling
+                for request in &mut self.configuration_requests {
+                    if request.coap.message.get_token() == packet.get_token() {
+                        request.complete(packet.clone());
                     }
                 }
-                Err(_) => panic!(),
+                self.last_configuration = Some(Instant::now());
+                self.configuration_scroll.add_content();
             }
         }
-        {
+    }
+
+    /// Writes are dropped while the device is disconnected
+    fn write_to_device(&mut self, data: &[u8]) {
+        if let Some(port) = &mut self.write_port {
+            let _ = port.write(data);
+            let _ = port.flush();
+            self.log_frame(true, encoded_frame_info(data));
+        }
+    }
+
+    /// Adds a frame to the slipmux frame view and counts it for its direction,
+    /// bytes outside of frames go to the raw serial view
+    fn log_frame(&mut self, sent: bool, frame: FrameInfo) {
+        let arrow = if sent { "←" } else { "→" };
+        let text = match frame {
+            FrameInfo::Frame(frame_type, length) => {
+                let (frames, bytes) = if sent {
+                    &mut self.frames_sent
+                } else {
+                    &mut self.frames_received
+                };
+                *frames += 1;
+                *bytes += length;
+                format!(
+                    "{arrow} 0x{frame_type:02x} {:<13} {length} bytes",
+                    channel_name(frame_type)
+                )
+            }
+            FrameInfo::Raw(bytes) => {
+                if let Some(raw) = &mut self.raw_serial {
+                    raw.extend(bytes);
+                    // Whole hexdump lines, so the remaining ones do not shift
+                    let excess = raw.len().saturating_sub(RAW_SERIAL_SIZE);
+                    raw.drain(..excess.next_multiple_of(16).min(raw.len()));
+                }
+                return;
+            }
+            FrameInfo::Error => {
+                self.link_health.add_framing_error();
+                format!(
+                    "{arrow} framing error #{}",
+                    self.link_health.framing_errors()
+                )
+            }
+        };
+        if self.frame_log.len() == FRAME_LOG_SIZE {
+            self.frame_log.pop_front();
+        }
+        self.frame_log.push_back((sent, text));
+    }
+
+    /// Takes at most one message of every channel of the read thread.
+    /// Returns true if anything changed.
+    ///
+    /// Once the port rejects its settings the read thread ends and the
+    /// channels are closed, the error stays in the footer.
+    fn receive(&mut self) -> bool {
+        let mut changed = false;
+        if let Ok(event) = self.connection_channel.try_recv() {
+            self.on_connection_event(event);
+            changed = true;
+        }
+        if let Ok(data) = self.diagnostic_channel.try_recv() {
+            if let Some(output) = self.diagnostic_messages.last_mut() {
+                output.push_str(&data);
+            }
+            self.trim_scrollback();
+            self.last_diagnostic = Some(Instant::now());
+            self.diagnostic_scroll.add_content();
+            self.link_health.add_diagnostic(&data);
+            self.statistics.add_diagnostic_received(data.len());
+            changed = true;
+        }
+        if let Ok(data) = self.configuration_channel.try_recv() {
+            self.on_configuration_msg(&data);
+            self.last_configuration = Some(Instant::now());
+            self.configuration_scroll.add_content();
+            changed = true;
+        }
+        if let Ok(frame) = self.frame_channel.try_recv() {
+            let raw = matches!(frame, FrameInfo::Raw(_));
+            self.log_frame(false, frame);
+            changed |= self.show_frame_log || raw && self.show_raw_serial;
+        }
+        if self.packet_channel.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+
+    /// The footer shows the state, `write_port` is only set while connected.
+    /// The device may have been flashed in between, so every connect
+    /// discovers its resources again.
+    fn on_connection_event(&mut self, event: ConnectionEvent) {
+        match event {
+            ConnectionEvent::Connected(port) => {
+                self.write_port = Some(port);
+                self.refresh_info();
+            }
+            ConnectionEvent::Disconnected => self.write_port = None,
+            ConnectionEvent::Rejected(err) => {
+                self.connection_error = Some(err);
+                self.write_port = None;
+            }
+        }
+    }
+
+    /// Requests version, board and network configuration for the info pane,
+    /// the responses are picked up in `on_configuration_msg`
+    fn refresh_info(&mut self) {
+        for path in INFO_PATHS {
             let mut request: CoapRequest<String> = CoapRequest::new();
             request.set_method(Method::Get);
-            request.set_path("/.well-known/ifconfig");
-            request.message.add_option(CoapOption::Block2, vec![0x05]);
-            let (data, size) = send_configuration(&request.message);
-            let _ = self.write_port.write(&data[..size]);
-
-            match self.configuration_channel.recv() {
-                Ok(data) => {
-                    let response = Packet::from_bytes(&data);
-                    if response.is_ok() {
-                        _ = write!(
-                            self.ip,
-                            "{:}",
-                            String::from_utf8_lossy(&response.unwrap().payload)
-                        );
-                    } else {
-                        _ = write!(self.ip, "Failed to parse ifconfig packet");
+            request.set_path(path);
+            self.send_request(request);
+        }
+    }
+
+    fn on_info_response(&mut self, path: &str, payload: &[u8]) {
+        let text = String::from_utf8_lossy(payload).trim().to_string();
+        match path {
+            // e.g. "RIOT (2024.01-devel)", only the part in parentheses is interesting
+            "riot/ver" => {
+                self.version = text
+                    .split_once('(')
+                    .and_then(|(_, rest)| rest.split_once(')'))
+                    .map_or(text.clone(), |(version, _)| version.to_string());
+            }
+            "riot/board" => self.board = text,
+            ".well-known/ifconfig" => self.ip = text,
+            // Resources the device announces are worth completing
+            ".well-known/core" => {
+                self.resources = parse_link_format(payload);
+                self.complete_resources();
+            }
+            // Filtered discovery only finds some resources, they add to the known ones
+            filtered if filtered.starts_with(".well-known/core?") => {
+                for link in parse_link_format(payload) {
+                    if !self.resources.iter().any(|known| known.path == link.path) {
+                        self.resources.push(link);
                     }
                 }
-                Err(_) => panic!(),
+                self.complete_resources();
             }
+            _ => {}
         }
     }
 
-    fn suggest_cmd(&self, cmd: &String) -> Option<String> {
-        for known_cmd in &self.autocomplete {
-            if known_cmd.starts_with(cmd) {
-                return Some(known_cmd.clone());
-            };
+    /// Offers the discovered resources for completion
+    fn complete_resources(&mut self) {
+        for link in &self.resources {
+            if link.path.starts_with('/') && !self.autocomplete.contains(&link.path) {
+                self.autocomplete.push(link.path.clone());
+            }
+        }
+    }
+
+    /// Tells the user how long a pane has been silent, once it exceeds `stale_after`
+    fn stale_hint(&self, last_update: Option<Instant>) -> String {
+        match (self.stale_after, last_update) {
+            (Some(threshold), Some(last_update)) if last_update.elapsed() > threshold => {
+                format!(" (last update {}s ago)", last_update.elapsed().as_secs())
+            }
+            _ => String::new(),
         }
-        None
+    }
+
+    fn insert_input(&mut self, c: char) {
+        self.user_command.insert(self.input_position, c);
+        self.input_position += c.len_utf8();
+    }
+
+    /// Pasted text is inserted as a whole, a newline in it does not submit
+    fn on_paste(&mut self, text: &str) -> Refresh {
+        if self.reverse_search.is_some()
+            || self.diagnostic_filter.as_ref().is_some_and(|f| f.editing)
+            || self.request_search.as_ref().is_some_and(|s| s.editing)
+        {
+            return Refresh::Skip;
+        }
+        // Terminals send a carriage return for line breaks
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.user_command.insert_str(self.input_position, &text);
+        self.input_position += text.len();
+        Refresh::Update
+    }
+
+    /// Replaces the input and puts the cursor at its end
+    fn set_user_command(&mut self, text: String) {
+        self.input_position = text.len();
+        self.user_command = text;
+    }
+
+    fn complete_input(&mut self) {
+        if let Some(cmd) = self.suggest_cmd(&self.user_command) {
+            // An alias looks like any other word, tell the user what it stands for
+            if let Some(command) = self.aliases.command(&cmd) {
+                self.status = format!("alias for '{command}'");
+            }
+            self.set_user_command(cmd);
+        }
+    }
+
+    fn suggest_cmd(&self, cmd: &str) -> Option<String> {
+        self.autocomplete
+            .iter()
+            .map(String::as_str)
+            .chain(self.aliases.names())
+            .find(|known_cmd| known_cmd.starts_with(cmd))
+            .map(str::to_string)
     }
 
     fn on_key(&mut self, key: KeyEvent) -> Refresh {
+        // Not re-mappable, so there is always a way out
         if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
-            return Refresh::Quit;
+            if self.confirm_quit() {
+                return Refresh::Quit;
+            }
+            return Refresh::Update;
+        }
+        let action = self.keymap.action(&key);
+        // Universal way back to normal, even from within a search or filter
+        if action == Some(Action::JumpToBottom) {
+            return self.on_action(Action::JumpToBottom);
+        }
+
+        if self.reverse_search.is_some() {
+            return self.on_key_reverse_search(key);
+        }
+        if self.diagnostic_filter.as_ref().is_some_and(|f| f.editing) {
+            return self.on_key_filter(key);
+        }
+        if self.request_search.as_ref().is_some_and(|s| s.editing) {
+            return self.on_key_request_search(key);
+        }
+        if self.resource_tree.is_some() && action != Some(Action::ToggleResourceTree) {
+            return self.on_key_resource_tree(key);
         }
 
         let update = match &self.focus {
-            ElementInFocus::UserInput => match key.code {
-                KeyCode::Esc => return Refresh::Quit,
-                KeyCode::Enter => {
-                    if !self.user_command.starts_with('/') {
+            ElementInFocus::UserInput => match (action, key.code) {
+                (Some(action), _) => return self.on_action(action),
+                (None, KeyCode::Char(to_insert)) => {
+                    self.insert_input(to_insert);
+                    true
+                }
+                (None, _) => false,
+            },
+        };
+
+        if update {
+            Refresh::Update
+        } else {
+            Refresh::Skip
+        }
+    }
+
+    fn on_action(&mut self, action: Action) -> Refresh {
+        match action {
+            Action::Quit => {
+                // Clears an active selection, search or filter before it quits
+                if self.diagnostic_selection.take().is_some()
+                    || self.request_search.take().is_some()
+                {
+                    return Refresh::Update;
+                }
+                if self.diagnostic_filter.is_none() {
+                    if self.confirm_quit() {
+                        return Refresh::Quit;
+                    }
+                    return Refresh::Update;
+                }
+                self.diagnostic_filter = None;
+                self.diagnostic_scroll.scroll_to_bottom();
+            }
+            Action::JumpToBottom => {
+                self.focus = ElementInFocus::UserInput;
+                self.diagnostic_scroll.scroll_to_bottom();
+                self.configuration_scroll.scroll_to_bottom();
+            }
+            Action::ReverseSearch => {
+                self.reverse_search = Some(ReverseSearch::new(self.user_command.clone()));
+            }
+            Action::Filter => {
+                self.diagnostic_filter
+                    .get_or_insert_with(DiagnosticFilter::new)
+                    .editing = true;
+            }
+            Action::SearchRequests => {
+                self.request_search
+                    .get_or_insert_with(DiagnosticFilter::new)
+                    .editing = true;
+            }
+            Action::NextMatch => self.select_match(1),
+            Action::PreviousMatch => self.select_match(-1),
+            Action::SelectNext => self.select_request(1),
+            Action::SelectPrevious => self.select_request(-1),
+            Action::CopyResponse => self.copy_selected_response(),
+            Action::SelectLineUp => self.extend_selection(-1),
+            Action::SelectLineDown => self.extend_selection(1),
+            Action::CopySelection => self.copy_diagnostic_selection(),
+            Action::ToggleHexdump => {
+                let Some(index) = self.selected_request else {
+                    self.status = "Select a request with Alt+J/Alt+K first".to_string();
+                    return Refresh::Update;
+                };
+                let request = &mut self.configuration_requests[index];
+                request.hexdump = !request.hexdump;
+            }
+            Action::ToggleOptions => {
+                let Some(index) = self.selected_request else {
+                    self.status = "Select a request with Alt+J/Alt+K first".to_string();
+                    return Refresh::Update;
+                };
+                let request = &mut self.configuration_requests[index];
+                request.show_options = !request.show_options;
+            }
+            Action::ToggleShellDecoding => self.decode_shell_output = !self.decode_shell_output,
+            Action::ToggleEcho => self.echo_commands = !self.echo_commands,
+            Action::ToggleCollapseRepeats => self.collapse_repeats = !self.collapse_repeats,
+            Action::ToggleFrameLog => self.show_frame_log = !self.show_frame_log,
+            Action::ToggleRawSerial => {
+                if self.raw_serial.is_some() {
+                    self.show_raw_serial = !self.show_raw_serial;
+                } else {
+                    self.status = "Start Jelly with --raw-serial to keep those bytes".to_string();
+                }
+            }
+            Action::CycleFrameFilter => {
+                self.frame_filter = self.frame_filter.next();
+                self.show_frame_log = true;
+            }
+            Action::ShrinkLeftPane => {
+                self.split_percent = self.split_percent.saturating_sub(SPLIT_STEP).max(MIN_SPLIT);
+            }
+            Action::GrowLeftPane => {
+                self.split_percent = (self.split_percent + SPLIT_STEP).min(100 - MIN_SPLIT);
+            }
+            Action::ToggleStatistics => self.show_statistics = !self.show_statistics,
+            Action::ToggleResourceTree => {
+                if self.resource_tree.take().is_none() {
+                    if self.resources.is_empty() {
+                        self.status = "No resources discovered yet, F8 asks the device".to_string();
+                    } else {
+                        self.resource_tree = Some(0);
+                    }
+                }
+            }
+            Action::SwitchScrollTarget => {
+                self.scroll_target = match self.scroll_target {
+                    ScrollTarget::Diagnostic => ScrollTarget::Configuration,
+                    ScrollTarget::Configuration => ScrollTarget::Diagnostic,
+                };
+            }
+            Action::ToggleCompactCbor => self.compact_cbor = !self.compact_cbor,
+            Action::ToggleErrorsOnly => {
+                self.errors_only = !self.errors_only;
+                // Do not keep a selection that can no longer be seen
+                if let Some(index) = self.selected_request {
+                    if !self.is_request_shown(&self.configuration_requests[index]) {
+                        self.selected_request = None;
+                    }
+                }
+                self.configuration_scroll.scroll_to_bottom();
+            }
+            Action::SaveScreen => self.screenshot_requested = true,
+            Action::RefreshInfo => self.refresh_info(),
+            Action::SaveSession => {
+                self.status = match self.save_session() {
+                    Ok(file_name) => format!("Saved session to {file_name}"),
+                    Err(err) => format!("Failed to save session: {err}"),
+                };
+            }
+            Action::Clear => self.clear_target(),
+            Action::PageUp => {
+                let height = self.target_area().height.into();
+                self.target_scroll().scroll_page_up(height);
+            }
+            Action::PageDown => {
+                let height = self.target_area().height.into();
+                self.target_scroll().scroll_page_down(height);
+            }
+            Action::ScrollToTop => self.target_scroll().scroll_to_top(),
+            Action::ScrollToBottom => self.target_scroll().scroll_to_bottom(),
+            Action::Submit => {
+                if let Some(expanded) = self.aliases.expand(&self.user_command) {
+                    self.set_user_command(expanded);
+                }
+                match classify_input(&self.user_command) {
+                    InputType::Diagnostic => {
                         if !self.user_command.ends_with('\n') {
                             self.user_command.push('\n');
                         }
-                        let (data, size) = send_diagnostic(&self.user_command);
-                        let _ = self.write_port.write(&data[..size]);
-                    } else {
+                        // Diagnostic output is not correlated to the command that caused
+                        // it, assume everything until the next command belongs to it.
+                        self.diagnostic_messages
+                            .push(DiagnosticOutput::new(Some(&self.user_command)));
+                        for frame in send_diagnostic(&self.user_command) {
+                            self.write_to_device(&frame);
+                        }
+                        self.statistics.add_diagnostic_sent(self.user_command.len());
+                        // The output of the command is what the user wants to see next
+                        self.diagnostic_scroll.scroll_to_bottom();
+                    }
+                    InputType::Coap {
+                        method,
+                        path,
+                        payload,
+                        confirmable,
+                    } => {
                         let mut request: CoapRequest<String> = CoapRequest::new();
-                        request.set_method(Method::Get);
-                        request.set_path(&self.user_command);
-                        request.message.set_token(self.get_new_token());
-                        request.message.add_option(CoapOption::Block2, vec![0x05]);
-                        let (data, size) = send_configuration(&request.message);
-                        self.configuration_packets.push(request.message.clone());
-                        let _ = self.write_port.write(&data[..size]);
-                        self.configuration_requests.push(request);
+                        request.set_method(method);
+                        set_target(&mut request, &path);
+                        if !confirmable {
+                            request.message.header.set_type(MessageType::NonConfirmable);
+                        }
+                        if let Some((payload, content_format)) = payload {
+                            request.message.set_content_format(content_format);
+                            request.message.payload = payload;
+                        }
+                        self.send_request(request);
+                        self.configuration_scroll.scroll_to_bottom();
                     }
-                    let _ = self.write_port.flush();
-                    if self.user_command != "\n" {
-                        self.user_commands.push(self.user_command.clone());
-                        self.user_command_cursor = self.user_commands.len();
+                    InputType::Observe { path, register } => {
+                        if register {
+                            let mut request: CoapRequest<String> = CoapRequest::new();
+                            request.set_method(Method::Get);
+                            set_target(&mut request, &path);
+                            request.message.set_observe_value(0);
+                            self.send_request(request);
+                        } else if !self.cancel_observation(&path) {
+                            self.status = format!("{path} is not being observed");
+                            return Refresh::Update;
+                        }
+                        self.configuration_scroll.scroll_to_bottom();
+                    }
+                    InputType::Rerun(index) => return self.rerun(index),
+                    InputType::Scan => {
+                        if !self.start_scan() {
+                            return Refresh::Update;
+                        }
+                        self.configuration_scroll.scroll_to_bottom();
+                    }
+                    InputType::Ping { path, count } => {
+                        if self.ping.is_some() {
+                            self.status = "A ping is already running".to_string();
+                            return Refresh::Update;
+                        }
+                        self.ping = Some(Ping::new(path, count));
+                        self.configuration_scroll.scroll_to_bottom();
+                    }
+                    InputType::Raw(bytes) => {
+                        self.send_raw(bytes);
+                        self.configuration_scroll.scroll_to_bottom();
+                    }
+                    InputType::Invalid(err) => {
+                        // Keep the input so it can be fixed
+                        self.status = err;
+                        return Refresh::Update;
                     }
-                    self.user_command.clear();
-                    true
                 }
-                KeyCode::Backspace => {
-                    self.user_command.pop();
-                    true
+                if self.user_command != "\n" {
+                    self.user_commands.push(self.user_command.clone());
+                    self.user_command_cursor = self.user_commands.len();
                 }
-                KeyCode::Up => {
-                    if self.user_command_cursor > 0 {
-                        self.user_command.clear();
-                        self.user_command_cursor -= 1;
-                        self.user_command = self.user_commands[self.user_command_cursor].clone();
+                self.set_user_command(String::new());
+            }
+            Action::DeleteBackward => {
+                let start = previous_grapheme_boundary(&self.user_command, self.input_position);
+                self.user_command.drain(start..self.input_position);
+                self.input_position = start;
+            }
+            Action::InsertNewline => self.insert_input('\n'),
+            Action::DeleteWordBackward => {
+                let start = previous_word_boundary(&self.user_command, self.input_position);
+                self.killed = self
+                    .user_command
+                    .drain(start..self.input_position)
+                    .collect();
+                self.input_position = start;
+            }
+            Action::DeleteToStart => {
+                self.killed = self.user_command.drain(..self.input_position).collect();
+                self.input_position = 0;
+            }
+            Action::DeleteToEnd => {
+                self.killed = self.user_command.split_off(self.input_position);
+            }
+            Action::Yank => {
+                self.user_command
+                    .insert_str(self.input_position, &self.killed);
+                self.input_position += self.killed.len();
+            }
+            Action::InputStart => self.input_position = 0,
+            Action::InputEnd => self.input_position = self.user_command.len(),
+            Action::CursorLeft => {
+                self.input_position =
+                    previous_grapheme_boundary(&self.user_command, self.input_position);
+            }
+            Action::CursorRight => {
+                // Like in a shell, Right at the end accepts the suggestion
+                if self.input_position == self.user_command.len() {
+                    if !self.user_command.is_empty() {
+                        self.complete_input();
                     }
-                    true
+                } else {
+                    self.input_position =
+                        next_grapheme_boundary(&self.user_command, self.input_position);
                 }
-                KeyCode::Down => {
-                    if self.user_command_cursor < self.user_commands.len() {
-                        self.user_command.clear();
-                        self.user_command_cursor += 1;
-                        if self.user_command_cursor == self.user_commands.len() {
-                            self.user_command.clear();
-                        } else {
-                            self.user_command =
-                                self.user_commands[self.user_command_cursor].clone();
-                        }
-                    }
-                    true
+            }
+            Action::WordLeft => {
+                self.input_position =
+                    previous_word_boundary(&self.user_command, self.input_position);
+            }
+            Action::WordRight => {
+                self.input_position = next_word_boundary(&self.user_command, self.input_position);
+            }
+            Action::HistoryPrevious => {
+                if self.user_command_cursor > 0 {
+                    self.user_command_cursor -= 1;
+                    self.set_user_command(self.user_commands[self.user_command_cursor].clone());
                 }
-                KeyCode::Tab => {
-                    match self.suggest_cmd(&self.user_command) {
-                        Some(cmd) => self.user_command = cmd,
-                        None => {}
+            }
+            Action::HistoryNext => {
+                if self.user_command_cursor < self.user_commands.len() {
+                    self.user_command_cursor += 1;
+                    if self.user_command_cursor == self.user_commands.len() {
+                        self.set_user_command(String::new());
+                    } else {
+                        self.set_user_command(self.user_commands[self.user_command_cursor].clone());
                     }
-                    true
                 }
-                KeyCode::Char(to_insert) => {
-                    self.user_command.push(to_insert);
-                    true
+            }
+            Action::Complete => self.complete_input(),
+        }
+        Refresh::Update
+    }
+
+    fn on_key_reverse_search(&mut self, key: KeyEvent) -> Refresh {
+        let Some(search) = &mut self.reverse_search else {
+            return Refresh::Skip;
+        };
+        match key.code {
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                search.next_older(&self.user_commands);
+            }
+            KeyCode::Char(to_insert) => {
+                search.query.push(to_insert);
+                search.refresh(&self.user_commands);
+            }
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.found = None;
+                search.refresh(&self.user_commands);
+            }
+            KeyCode::Enter => {
+                if let Some(index) = search.found {
+                    self.set_user_command(self.user_commands[index].clone());
+                    self.user_command_cursor = index;
                 }
-                _ => false,
-            },
+                self.reverse_search = None;
+            }
+            KeyCode::Esc => {
+                let saved_input = search.saved_input.clone();
+                self.set_user_command(saved_input);
+                self.reverse_search = None;
+            }
+            // Arrows, Tab and the like would conflict with the search
+            _ => return Refresh::Skip,
+        }
+        Refresh::Update
+    }
+
+    fn target_scroll(&mut self) -> &mut PaneScroll {
+        match self.scroll_target {
+            ScrollTarget::Diagnostic => &mut self.diagnostic_scroll,
+            ScrollTarget::Configuration => &mut self.configuration_scroll,
+        }
+    }
+
+    fn target_area(&self) -> Rect {
+        match self.scroll_target {
+            ScrollTarget::Diagnostic => self.diagnostic_area,
+            ScrollTarget::Configuration => self.configuration_area,
+        }
+    }
+
+    /// Picks the plain variant of a status marker if emoji are disabled
+    fn glyph(&self, fancy: &'static str, plain: &'static str) -> &'static str {
+        if self.ascii {
+            plain
+        } else {
+            fancy
+        }
+    }
+
+    /// Marker of pending requests, a spinner advancing with every draw or
+    /// growing dots on plain terminals
+    fn spinner(&self) -> String {
+        const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        if self.ascii || !self.color {
+            // Idle redraws happen about 20 times a second, too fast to count dots
+            ".".repeat(self.spinner_tick / 5 % 3 + 1)
+        } else {
+            SPINNER[self.spinner_tick % SPINNER.len()].to_string()
+        }
+    }
+
+    /// Pane titles are bold while keyboard scrolling applies to them and
+    /// point out new content below the visible part
+    fn pane_title(&self, title: &'static str, target: ScrollTarget) -> Span<'static> {
+        let scroll = match target {
+            ScrollTarget::Diagnostic => &self.diagnostic_scroll,
+            ScrollTarget::Configuration => &self.configuration_scroll,
         };
+        let title = if scroll.unseen {
+            format!("{title} [new below]")
+        } else {
+            title.to_string()
+        };
+        if self.scroll_target == target {
+            Span::styled(title, Style::new().bold())
+        } else {
+            Span::from(title)
+        }
+    }
 
-        if update {
-            Refresh::Update
+    fn on_key_filter(&mut self, key: KeyEvent) -> Refresh {
+        let Some(filter) = &mut self.diagnostic_filter else {
+            return Refresh::Skip;
+        };
+        match key.code {
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                filter.ignore_case = !filter.ignore_case;
+            }
+            KeyCode::Char(to_insert) => filter.needle.push(to_insert),
+            KeyCode::Backspace => _ = filter.needle.pop(),
+            KeyCode::Enter => filter.editing = false,
+            KeyCode::Esc => self.diagnostic_filter = None,
+            _ => return Refresh::Skip,
+        }
+        // The previous position is meaningless for a different set of lines
+        self.diagnostic_scroll.scroll_to_bottom();
+        Refresh::Update
+    }
+
+    fn on_key_request_search(&mut self, key: KeyEvent) -> Refresh {
+        let Some(search) = &mut self.request_search else {
+            return Refresh::Skip;
+        };
+        match key.code {
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                search.ignore_case = !search.ignore_case;
+            }
+            KeyCode::Char(to_insert) => search.needle.push(to_insert),
+            KeyCode::Backspace => _ = search.needle.pop(),
+            KeyCode::Enter => {
+                search.editing = false;
+                // Starts over at the oldest match
+                self.selected_request = None;
+                self.select_match(1);
+            }
+            KeyCode::Esc => self.request_search = None,
+            _ => return Refresh::Skip,
+        }
+        Refresh::Update
+    }
+
+    fn on_key_resource_tree(&mut self, key: KeyEvent) -> Refresh {
+        let Some(selected) = self.resource_tree else {
+            return Refresh::Skip;
+        };
+        let rows = resource_tree(&self.resources);
+        match key.code {
+            KeyCode::Up => self.resource_tree = Some(selected.saturating_sub(1)),
+            KeyCode::Down => {
+                self.resource_tree = Some((selected + 1).min(rows.len().saturating_sub(1)));
+            }
+            KeyCode::Enter => {
+                // Groups are only part of the path of the resources below them
+                let Some(link) = rows.get(selected).and_then(|row| row.link) else {
+                    return Refresh::Skip;
+                };
+                self.set_user_command(self.resources[link].path.clone());
+                self.resource_tree = None;
+            }
+            KeyCode::Esc => self.resource_tree = None,
+            _ => return Refresh::Skip,
+        }
+        Refresh::Update
+    }
+
+    /// Visible requests whose request or response contains the search query,
+    /// either as displayed or in the raw payload, e.g. text inside a hexdump
+    fn request_matches(&self) -> Vec<usize> {
+        let Some(search) = &self.request_search else {
+            return vec![];
+        };
+        if search.needle.is_empty() {
+            return vec![];
+        }
+        self.configuration_requests
+            .iter()
+            .enumerate()
+            .filter(|(_, req)| self.is_request_shown(req))
+            .filter(|(_, req)| {
+                let mut text = fmt_packet_header(&req.coap.message);
+                _ = write!(
+                    text,
+                    "\n{}",
+                    String::from_utf8_lossy(&req.coap.message.payload)
+                );
+                if let Some(response) = &req.coap.response {
+                    let message = &response.message;
+                    _ = write!(
+                        text,
+                        "\n{}\n{}",
+                        fmt_packet(message, self.compact_cbor),
+                        String::from_utf8_lossy(&message.payload)
+                    );
+                }
+                text.lines()
+                    .any(|line| !search.find_matches(line).is_empty())
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Selects the next (`step` 1) or previous (-1) match after the selected
+    /// request, wrapping around at the ends, and scrolls it into view
+    fn select_match(&mut self, step: isize) {
+        let matches = self.request_matches();
+        if matches.is_empty() {
+            self.status = "No matching requests".to_string();
+            return;
+        }
+        let selected = self.selected_request;
+        let position = match (step > 0, selected) {
+            (true, Some(selected)) => matches.iter().position(|index| *index > selected),
+            (false, Some(selected)) => matches.iter().rposition(|index| *index < selected),
+            (true, None) => Some(0),
+            (false, None) => None,
+        };
+        let position = position.unwrap_or(if step > 0 { 0 } else { matches.len() - 1 });
+        self.selected_request = Some(matches[position]);
+        self.reveal_selection = true;
+        self.status = format!("Match {} of {}", position + 1, matches.len());
+    }
+
+    /// Moves the request selection by `step`, starting at the newest request
+    fn select_request(&mut self, step: isize) {
+        let visible: Vec<usize> = self
+            .configuration_requests
+            .iter()
+            .enumerate()
+            .filter(|(_, req)| self.is_request_shown(req))
+            .map(|(index, _)| index)
+            .collect();
+        if visible.is_empty() {
+            return;
+        }
+        let position = match self
+            .selected_request
+            .and_then(|selected| visible.iter().position(|index| *index == selected))
+        {
+            Some(position) => position.saturating_add_signed(step).min(visible.len() - 1),
+            None => visible.len() - 1,
+        };
+        self.selected_request = Some(visible[position]);
+    }
+
+    /// Writes all diagnostic output and CoAP exchanges with their timestamps,
+    /// as JSON if the file name ends in `.json`, otherwise as plain text
+    fn save_session(&self) -> io::Result<String> {
+        let path = self.session_file.clone().unwrap_or_else(|| {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            PathBuf::from(format!("jelly-session-{timestamp}.txt"))
+        });
+
+        let mut entries = vec![];
+        for output in &self.diagnostic_messages {
+            if output.command().is_none() && output.text().is_empty() {
+                continue;
+            }
+            entries.push(SessionEntry {
+                time: output.started(),
+                kind: "diagnostic",
+                summary: output.command().unwrap_or_default().to_string(),
+                text: output.text().to_string(),
+                frame: None,
+            });
+        }
+        for request in &self.configuration_requests {
+            entries.push(SessionEntry {
+                time: request.sent_at,
+                kind: "request",
+                summary: fmt_packet_header(&request.coap.message).trim().to_string(),
+                text: String::new(),
+                frame: request.coap.message.to_bytes().ok(),
+            });
+            if let (Some(response), Some(time)) = (&request.coap.response, request.received_at) {
+                let message = &response.message;
+                let text = match message.get_content_format() {
+                    Some(
+                        cf @ (ContentFormat::TextPlain
+                        | ContentFormat::ApplicationLinkFormat
+                        | ContentFormat::ApplicationCBOR),
+                    ) => fmt_payload(message, cf, self.compact_cbor),
+                    // Hex like the frames, which `--replay` reads back
+                    Some(_) => fmt_hex(&message.payload),
+                    None => "Empty Payload".to_string(),
+                };
+                entries.push(SessionEntry {
+                    time,
+                    kind: "response",
+                    summary: fmt_packet_header(message).trim().to_string(),
+                    text,
+                    frame: message.to_bytes().ok(),
+                });
+            }
+        }
+        entries.sort_by_key(|entry| entry.time);
+
+        let content = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            fmt_session_json(&entries, &self.timestamps)
         } else {
-            Refresh::Skip
+            fmt_session_text(&entries, &self.timestamps)
+        };
+        fs::write(&path, content)?;
+        Ok(path.display().to_string())
+    }
+
+    /// Drops the oldest diagnostic lines beyond `scrollback`, whole outputs
+    /// first, but never the latest one as more text may still arrive for it
+    fn trim_scrollback(&mut self) {
+        let total: usize = self
+            .diagnostic_messages
+            .iter()
+            .map(DiagnosticOutput::line_count)
+            .sum();
+        let mut excess = total.saturating_sub(self.scrollback);
+        if excess == 0 {
+            return;
+        }
+        let mut dropped = 0;
+        while excess > 0 {
+            let oldest = self.diagnostic_messages[0].line_count();
+            if self.diagnostic_messages.len() > 1 && oldest <= excess {
+                let removed = self.diagnostic_messages.remove(0);
+                excess -= oldest;
+                dropped += oldest;
+                // Its echoed command disappears along with it
+                if self.echo_commands && removed.command_line().is_some() {
+                    dropped += 1;
+                }
+            } else {
+                dropped += self.diagnostic_messages[0].drop_lines(excess);
+                break;
+            }
+        }
+        self.diagnostic_trimmed = true;
+        // Keeps the lines the user scrolled to in view, and selected
+        self.diagnostic_scroll.remove_lines(dropped);
+        if let Some((anchor, cursor)) = &mut self.diagnostic_selection {
+            *anchor = anchor.saturating_sub(dropped);
+            *cursor = cursor.saturating_sub(dropped);
+        }
+    }
+
+    /// Empties the pane that keyboard scrolling applies to
+    fn clear_target(&mut self) {
+        match self.scroll_target {
+            ScrollTarget::Diagnostic => {
+                // Output that is still arriving belongs to the latest command
+                let latest = self.diagnostic_messages.len() - 1;
+                self.diagnostic_messages.drain(..latest);
+                self.diagnostic_messages[0].clear();
+                self.diagnostic_trimmed = false;
+                self.diagnostic_selection = None;
+            }
+            ScrollTarget::Configuration => {
+                // Active observations stay, so their notifications still
+                // have a request to go to and can be cancelled later
+                self.configuration_requests.retain(|req| req.observing);
+                self.log_notes.clear();
+                // The scan and the ping refer to the requests by index
+                self.scan = None;
+                self.ping = None;
+                self.selected_request = None;
+            }
+        }
+        self.target_scroll().scroll_to_bottom();
+    }
+
+    fn is_request_shown(&self, request: &Request) -> bool {
+        !self.errors_only || request.is_error()
+    }
+
+    fn copy_selected_response(&mut self) {
+        let Some(request) = self
+            .selected_request
+            .and_then(|index| self.configuration_requests.get(index))
+        else {
+            self.status = "No request selected, use Alt+J/Alt+K".to_string();
+            return;
+        };
+        let Some(response) = &request.coap.response else {
+            self.status = "The selected request has no response yet".to_string();
+            return;
+        };
+        let payload = match response.message.get_content_format() {
+            Some(cf) => fmt_payload(&response.message, cf, self.compact_cbor),
+            None => String::new(),
+        };
+        self.status = match copy_to_clipboard(payload) {
+            Ok(()) => "Copied response payload to the clipboard".to_string(),
+            Err(err) => format!("Failed to copy to the clipboard: {err}"),
+        };
+    }
+
+    /// Moves the cursor of the line selection in the diagnostic pane, starting
+    /// at the bottom line in view, and scrolls to keep the cursor visible
+    fn extend_selection(&mut self, step: isize) {
+        let count = self.diagnostic_text().lines.len();
+        if count == 0 {
+            return;
+        }
+        let height = usize::from(self.diagnostic_area.height).max(1);
+        let (anchor, cursor) = self.diagnostic_selection.unwrap_or_else(|| {
+            let bottom = (self.diagnostic_scroll.position + height - 1).min(count - 1);
+            (bottom, bottom)
+        });
+        let cursor = cursor.saturating_add_signed(step).min(count - 1);
+        self.diagnostic_selection = Some((anchor.min(count - 1), cursor));
+        if cursor < self.diagnostic_scroll.position {
+            self.diagnostic_scroll.scroll_to(cursor);
+        } else if cursor >= self.diagnostic_scroll.position + height {
+            self.diagnostic_scroll.scroll_to(cursor + 1 - height);
+        }
+    }
+
+    fn copy_diagnostic_selection(&mut self) {
+        let Some(range) = self.selected_lines() else {
+            self.status = "Select diagnostic lines with Shift+Up/Shift+Down first".to_string();
+            return;
+        };
+        let text: Vec<String> = self.diagnostic_text().lines[range]
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect()
+            })
+            .collect();
+        let count = text.len();
+        self.status = match copy_to_clipboard(text.join("\n")) {
+            Ok(()) => format!("Copied {count} lines to the clipboard"),
+            Err(err) => format!("Failed to copy to the clipboard: {err}"),
+        };
+    }
+
+    /// Selected diagnostic lines, clamped to the lines there are now
+    fn selected_lines(&self) -> Option<RangeInclusive<usize>> {
+        let (anchor, cursor) = self.diagnostic_selection?;
+        let last = self.diagnostic_text().lines.len().checked_sub(1)?;
+        Some(anchor.min(cursor).min(last)..=anchor.max(cursor).min(last))
+    }
+
+    /// Diagnostic output as shown in the pane, after collapsing and filtering
+    fn diagnostic_text(&self) -> Text<'static> {
+        let mut lines = vec![];
+        for output in &self.diagnostic_messages {
+            // A filter shows matching lines only
+            if let (true, None, Some(command)) = (
+                self.echo_commands,
+                &self.diagnostic_filter,
+                output.command_line(),
+            ) {
+                lines.push(Line::styled(format!("> {command}"), Style::new().bold()));
+            }
+            let text = output.render(self.decode_shell_output);
+            let text = if self.collapse_repeats {
+                collapse_repeats(&text)
+            } else {
+                text
+            };
+            match &self.diagnostic_filter {
+                Some(filter) => lines.extend(filtered_lines(&text, filter)),
+                None => lines.extend(Text::from(text).lines),
+            }
+        }
+        let mut text = Text::from(lines);
+        if self.diagnostic_trimmed {
+            text.lines.insert(
+                0,
+                Line::styled("(earlier output trimmed)", Style::new().dim()),
+            );
         }
+        text
+    }
+
+    fn on_mouse(&mut self, mouse: MouseEvent) -> Refresh {
+        let lines = match mouse.kind {
+            MouseEventKind::ScrollUp => -1,
+            MouseEventKind::ScrollDown => 1,
+            // A click only picks the pane keyboard scrolling applies to
+            MouseEventKind::Down(MouseButton::Left) => 0,
+            _ => return Refresh::Skip,
+        };
+        let position = Position::new(mouse.column, mouse.row);
+        if self.diagnostic_area.contains(position) {
+            self.scroll_target = ScrollTarget::Diagnostic;
+        } else if self.configuration_area.contains(position) {
+            self.scroll_target = ScrollTarget::Configuration;
+        } else {
+            return Refresh::Skip;
+        }
+        self.target_scroll().scroll(lines);
+        Refresh::Update
     }
 
     fn draw(&mut self, frame: &mut Frame) {
@@ -322,106 +1863,324 @@ impl App {
                 Constraint::Length(1),
             ],
         )
-        .split(frame.size());
+        .split(frame.area());
         frame.render_widget(
             Block::new()
                 .borders(Borders::TOP)
-                .title("Jelly 🪼: Friendly SLIPMUX for RIOT OS")
+                .title(format!(
+                    "Jelly{}: Friendly SLIPMUX for RIOT OS",
+                    self.glyph(" 🪼", "")
+                ))
                 .title_alignment(Alignment::Center),
             main_layout[0],
         );
         frame.render_widget(
             Block::new()
                 .borders(Borders::TOP)
-                .title(format!(
-                    "✅ connected via /dev/ttyACM0 with RIOT {}",
-                    self.version
-                ))
-                .title_alignment(Alignment::Right),
+                .title(if let Some(replay) = &self.replay {
+                    if replay.is_finished() {
+                        format!("{} replay finished", self.glyph("⏹", "[==]"))
+                    } else {
+                        format!(
+                            "{} replaying a saved session | {}",
+                            self.glyph("▶", "[>>]"),
+                            self.link_summary()
+                        )
+                    }
+                } else if let Some(err) = &self.connection_error {
+                    format!(
+                        "{} {} rejected the serial settings: {err}",
+                        self.glyph("❌", "[--]"),
+                        self.device
+                    )
+                } else if self.write_port.is_some() {
+                    format!(
+                        "{} connected via {} with RIOT {} | {}",
+                        self.glyph("✅", "[OK]"),
+                        self.device,
+                        self.version,
+                        self.link_summary()
+                    )
+                } else {
+                    format!("{} not connected, retrying..", self.glyph("❌", "[--]"))
+                })
+                .title_alignment(Alignment::Right)
+                .title(Line::from(self.status.as_str()).left_aligned())
+                .title(
+                    Line::from(
+                        self.link_health
+                            .warning()
+                            .map(|warning| format!("{} {warning}", self.glyph("⚠", "[!]")))
+                            .unwrap_or_default(),
+                    )
+                    .left_aligned()
+                    .style(self.theme.warning),
+                ),
             main_layout[2],
         );
 
         let horizontal_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .margin(0)
-            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .constraints(
+                [
+                    Constraint::Percentage(self.split_percent),
+                    Constraint::Percentage(100 - self.split_percent),
+                ]
+                .as_ref(),
+            )
             .split(main_layout[1]);
 
         let horizontal_chunk_left = horizontal_chunks[0];
         let horizontal_chunk_right = horizontal_chunks[1];
 
+        // The input box grows with its content, up to half of the pane
+        let input_width = usize::from(horizontal_chunk_right.width.saturating_sub(2)).max(1);
+        let editing_filter = self.diagnostic_filter.as_ref().filter(|f| f.editing);
+        let (input, cursor) = if let Some(filter) = editing_filter {
+            let case = if filter.ignore_case {
+                "ignoring case"
+            } else {
+                "Ctrl-F: ignore case"
+            };
+            (format!("(filter, {case}) '{}'", filter.needle), None)
+        } else if let Some(search) = self.request_search.as_ref().filter(|s| s.editing) {
+            let case = if search.ignore_case {
+                "ignoring case"
+            } else {
+                "Ctrl-F: ignore case"
+            };
+            let input = format!(
+                "(search requests, {case}) '{}': {} matches",
+                search.needle,
+                self.request_matches().len()
+            );
+            (input, None)
+        } else if let Some(search) = &self.reverse_search {
+            let (prompt, found) = match search.found {
+                Some(index) => ("reverse-i-search", self.user_commands[index].trim_end()),
+                None if search.query.is_empty() => ("reverse-i-search", ""),
+                None => ("failed reverse-i-search", ""),
+            };
+            (format!("({prompt}) '{}': {found}", search.query), None)
+        } else {
+            let before_cursor = wrap_input(&self.user_command[..self.input_position], input_width);
+            let row = before_cursor.len() - 1;
+            let column = Span::raw(before_cursor[row].as_str()).width();
+            // A full line pushes the next character, and the cursor, to the next one
+            let cursor = if column >= input_width {
+                (row + 1, 0)
+            } else {
+                (row, column)
+            };
+            (self.user_command.clone(), Some(cursor))
+        };
+        let mut input_lines = wrap_input(&input, input_width);
+        if cursor.is_some_and(|(row, _)| row == input_lines.len()) {
+            input_lines.push(String::new());
+        }
+        let input_height = u16::try_from(input_lines.len())
+            .unwrap_or(u16::MAX)
+            .min((horizontal_chunk_right.height / 2).saturating_sub(2))
+            .max(1);
+
         let right_chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(90), Constraint::Percentage(10)].as_ref())
+            .constraints([Constraint::Min(0), Constraint::Length(input_height + 2)].as_ref())
             .split(horizontal_chunk_right);
 
         let right_chunk_upper = right_chunks[0];
         let right_chunk_lower = right_chunks[1];
 
         let right_block_up = Block::bordered()
-            .title(vec![Span::from("Configuration Messages")])
+            .title(vec![
+                self.pane_title("Configuration Messages", ScrollTarget::Configuration),
+                Span::from(self.stale_hint(self.last_configuration)),
+                Span::from(if self.errors_only {
+                    let errors = self
+                        .configuration_requests
+                        .iter()
+                        .filter(|req| req.is_error())
+                        .count();
+                    format!(" [errors only: {errors}]")
+                } else {
+                    String::new()
+                }),
+            ])
             .title_alignment(Alignment::Left);
 
         let right_block_down = Block::bordered()
             .title(vec![Span::from("User Input")])
-            .title_alignment(Alignment::Left);
+            .title_alignment(Alignment::Left)
+            .title(Line::from("Ctrl+G: back to bottom").right_aligned());
 
-        let text: &str = &self.user_command;
-        let text = Text::from(text);
-        let paragraph = Paragraph::new(text).block(right_block_down);
+        let inner = right_block_down.inner(right_chunk_lower);
+        // Keeps the line with the cursor in view
+        let scroll = cursor.map_or(0, |(row, _)| {
+            row.saturating_sub(usize::from(inner.height).saturating_sub(1))
+        });
+        if let Some((row, column)) = cursor {
+            frame.set_cursor_position(Position::new(
+                inner.x + u16::try_from(column).unwrap_or(u16::MAX),
+                inner.y + u16::try_from(row - scroll).unwrap_or(u16::MAX),
+            ));
+        }
+        let lines: Vec<Line> = input_lines
+            .into_iter()
+            .skip(scroll)
+            .map(Line::from)
+            .collect();
+        let paragraph = Paragraph::new(lines).block(right_block_down);
         frame.render_widget(paragraph, right_chunk_lower);
 
         let mut state = ScrollViewState::default();
         let mut req_blocks = vec![];
         let mut constrains = vec![];
+        // Leaves room for the scrollbar, which is decided only after the layout
+        let title_width = right_block_up
+            .inner(right_chunk_upper)
+            .width
+            .saturating_sub(1)
+            .into();
+        self.spinner_tick = self.spinner_tick.wrapping_add(1);
+        let spinner = self.spinner();
+        for req in &mut self.configuration_requests {
+            req.render_response(self.compact_cbor, |packet, compact, hexdump| {
+                fmt_packet_lines(packet, compact, hexdump, &self.theme)
+            });
+        }
+        // Top of the selected request in the scroll view
+        let mut selected_offset = None;
         let total_length: u16 = {
             let mut sum = 0;
-            for req in &self.configuration_requests {
-                let option_list_ = req.message.get_option(CoapOption::UriPath).unwrap();
+            let mut notes = self.log_notes.iter().peekable();
+            for (index, req) in self.configuration_requests.iter().enumerate() {
+                while let Some((_, note)) = notes.next_if(|(position, _)| *position <= index) {
+                    let (paragraph, height) = fmt_log_note(note, &self.theme);
+                    req_blocks.push(paragraph);
+                    sum += height;
+                    constrains.push(Min(height.try_into().unwrap()));
+                }
+                if !self.is_request_shown(req) {
+                    continue;
+                }
+                if self.selected_request == Some(index) {
+                    selected_offset = Some(sum);
+                }
+                let title_style = if self.selected_request == Some(index) {
+                    Style::new().reversed()
+                } else {
+                    Style::new()
+                };
+                // Raw frames may come without a path
+                let option_list_ = req.coap.message.get_option(CoapOption::UriPath);
                 let mut uri_path = String::new();
-                for option in option_list_ {
+                for option in option_list_.into_iter().flatten() {
                     _ = write!(uri_path, "{}", String::from_utf8_lossy(option))
                 }
+                let pending = if req.timed_out && !req.is_confirmable() {
+                    Line::styled(
+                        format!(
+                            "No response within {}s, NON requests may go unanswered",
+                            self.coap_timeout.as_secs()
+                        ),
+                        Style::new().dim(),
+                    )
+                } else if req.timed_out {
+                    Line::styled(
+                        format!("Timed out after {}s", self.coap_timeout.as_secs()),
+                        self.theme.error,
+                    )
+                } else if let Some((sent, total)) = req.upload_progress() {
+                    Line::from(format!(
+                        "{spinner} Uploading blocks, {sent} of {total} bytes sent"
+                    ))
+                } else if !req.partial_payload.is_empty() {
+                    Line::from(format!(
+                        "{spinner} Receiving blocks, {} bytes so far",
+                        req.partial_payload.len()
+                    ))
+                } else {
+                    Line::from(format!("{spinner} Awaiting response"))
+                };
                 if uri_path.eq("configps") {
                     let block = Block::new()
                         .borders(Borders::TOP | Borders::BOTTOM)
                         .title(vec![Span::from("Command: ps")])
-                        .title_alignment(Alignment::Left);
-                    match &req.response {
+                        .title_alignment(Alignment::Left)
+                        .title_style(title_style);
+                    match &req.coap.response {
                         Some(resp) => {
-                            let text = fmt_ps(&resp.message);
+                            let text = fmt_ps(&resp.message, self.ps_sort);
                             let linecount = text.lines().count();
                             sum += linecount + 2;
                             constrains.push(Min((linecount + 2).try_into().unwrap()));
                             req_blocks.push(Paragraph::new(text).block(block));
                         }
                         None => {
-                            req_blocks.push(Paragraph::new("Awaiting response").block(block));
+                            req_blocks.push(Paragraph::new(pending).block(block));
                             sum += 3;
                             constrains.push(Min(3));
                         }
                     };
                 } else {
+                    let mut title = fmt_packet(&req.coap.message, true);
+                    if req.retransmissions > 0 {
+                        _ = write!(
+                            title,
+                            " {}{}",
+                            self.glyph("↻", "retry "),
+                            req.retransmissions
+                        );
+                    }
+                    if !req.is_confirmable() {
+                        title.push_str(" NON");
+                    }
+                    // A title wider than the pane would be clipped, hiding most of the
+                    // path, so it moves into the body where it can wrap
+                    let (title, mut lines) = if Span::raw(title.as_str()).width() > title_width {
+                        let lines = wrap_to_width(title.trim(), title_width)
+                            .into_iter()
+                            .map(|line| Line::styled(line, title_style))
+                            .collect();
+                        (String::new(), lines)
+                    } else {
+                        (title, vec![])
+                    };
                     let block = Block::new()
                         .borders(Borders::TOP | Borders::BOTTOM)
-                        .title(vec![Span::from(fmt_packet(&req.message))])
-                        .title_alignment(Alignment::Left);
-                    match &req.response {
-                        Some(resp) => {
-                            let text = fmt_packet(&resp.message);
-                            let linecount = text.lines().count();
-                            sum += linecount + 2;
-                            constrains.push(Min((linecount + 2).try_into().unwrap()));
-                            req_blocks.push(Paragraph::new(text).block(block));
-                        }
-                        None => {
-                            req_blocks.push(Paragraph::new("Awaiting response").block(block));
-                            sum += 3;
-                            constrains.push(Min(3));
-                        }
+                        .title(vec![Span::from(title)])
+                        .title_alignment(Alignment::Left)
+                        .title_style(title_style);
+                    if req.show_options {
+                        lines.extend(fmt_options(&req.coap.message, "←"));
+                    }
+                    match req.rendered_response() {
+                        Some(response) => lines.extend_from_slice(response),
+                        None => lines.push(pending),
                     };
+                    if let Some(response) = req.coap.response.as_ref().filter(|_| req.show_options)
+                    {
+                        lines.extend(fmt_options(&response.message, "→"));
+                    }
+                    if let Some(search) = &self.request_search {
+                        lines = lines
+                            .iter()
+                            .map(|line| highlight_matches(line, search))
+                            .collect();
+                    }
+                    let linecount = lines.len();
+                    sum += linecount + 2;
+                    constrains.push(Min((linecount + 2).try_into().unwrap()));
+                    req_blocks.push(Paragraph::new(lines).block(block));
                 }
             }
+            for (_, note) in notes {
+                let (paragraph, height) = fmt_log_note(note, &self.theme);
+                req_blocks.push(paragraph);
+                sum += height;
+                constrains.push(Min(height.try_into().unwrap()));
+            }
             sum.try_into().unwrap()
         };
 
@@ -431,12 +2190,15 @@ impl App {
             right_block_up.inner(right_chunk_upper).width
         };
 
-        if right_block_up.inner(right_chunk_upper).height < total_length {
-            let diff = total_length - right_block_up.inner(right_chunk_upper).height;
-            for _ in 0..diff {
-                state.scroll_down();
+        self.configuration_area = right_block_up.inner(right_chunk_upper);
+        let max_offset = total_length.saturating_sub(self.configuration_area.height);
+        if std::mem::take(&mut self.reveal_selection) {
+            if let Some(selected_offset) = selected_offset {
+                self.configuration_scroll.scroll_to(selected_offset);
             }
         }
+        let offset = self.configuration_scroll.offset(max_offset.into());
+        state.set_offset(Position::new(0, offset.try_into().unwrap()));
 
         let mut scroll_view = ScrollView::new(Size::new(width, total_length));
         let buf = scroll_view.buf_mut();
@@ -445,7 +2207,6 @@ impl App {
         for (a, req_b) in zip(areas, req_blocks) {
             req_b.render(a, buf);
         }
-        for _request in &self.configuration_requests {}
         frame.render_stateful_widget(
             scroll_view,
             right_block_up.inner(right_chunk_upper),
@@ -462,36 +2223,151 @@ impl App {
         let left_chunk_lower = left_chunks[1];
 
         let left_block_up = Block::bordered()
-            .title(vec![Span::from("Diagnostic Messages")])
+            .title(vec![
+                self.pane_title(
+                    if self.decode_shell_output {
+                        "Diagnostic Messages"
+                    } else {
+                        "Diagnostic Messages (raw)"
+                    },
+                    ScrollTarget::Diagnostic,
+                ),
+                Span::from(self.stale_hint(self.last_diagnostic)),
+                Span::from(
+                    self.diagnostic_filter
+                        .as_ref()
+                        .map(|filter| format!(" [filter: '{}']", filter.needle))
+                        .unwrap_or_default(),
+                ),
+            ])
             .title_alignment(Alignment::Left);
 
         let left_block_down = Block::bordered()
-            .title(vec![Span::from("Configuration")])
+            .title(vec![Span::from(if self.show_raw_serial {
+                "Raw Serial".to_string()
+            } else if self.show_frame_log {
+                let filter = match self.frame_filter {
+                    FrameFilter::Both => "",
+                    FrameFilter::Received => " [received only]",
+                    FrameFilter::Sent => " [sent only]",
+                };
+                format!(
+                    "Slipmux Frames → {} ({} bytes) ← {} ({} bytes){filter}",
+                    self.frames_received.0,
+                    self.frames_received.1,
+                    self.frames_sent.0,
+                    self.frames_sent.1
+                )
+            } else {
+                "Configuration".to_string()
+            })])
             .title_alignment(Alignment::Left);
 
-        let text: &str = &self.diagnostic_messages;
-        let text = Text::from(text);
-        let height = left_block_up.inner(left_chunk_upper).height;
-        let scroll = {
-            if text.height() > height as usize {
-                text.height() - height as usize
-            } else {
-                0
+        let mut text = self.diagnostic_text();
+        if let Some(range) = self.selected_lines() {
+            for line in &mut text.lines[range] {
+                *line = line.clone().patch_style(Style::new().reversed());
             }
-        };
+        }
+        self.diagnostic_area = left_block_up.inner(left_chunk_upper);
+        let height = self.diagnostic_area.height;
+        let scroll = self
+            .diagnostic_scroll
+            .offset(text.height().saturating_sub(height as usize));
         let paragraph = Paragraph::new(text).scroll((scroll as u16, 0));
         let paragraph_block = paragraph.block(left_block_up);
         frame.render_widget(paragraph_block, left_chunk_upper);
 
-        //let text: &str = &self.ip;
-        let text = format!(
-            "Version: {}\nBoard: {}\n{}",
-            self.version, self.board, self.ip
-        );
-        let text = Text::from(text);
-        let paragraph = Paragraph::new(text);
+        let paragraph = if let Some(raw) = self.raw_serial.as_ref().filter(|_| self.show_raw_serial)
+        {
+            // Only the newest bytes fit, older ones are cut off at the top
+            let height = left_block_down.inner(left_chunk_lower).height as usize;
+            let (first, second) = raw.as_slices();
+            let dump = fmt_hexdump(&[first, second].concat());
+            let skip = dump.len().saturating_sub(height);
+            let lines: Vec<Line> = dump.into_iter().skip(skip).map(Line::from).collect();
+            Paragraph::new(lines)
+        } else if self.show_frame_log {
+            // Only the newest frames fit, older ones are cut off at the top
+            let height = left_block_down.inner(left_chunk_lower).height as usize;
+            let shown: Vec<&String> = self
+                .frame_log
+                .iter()
+                .filter(|(sent, _)| self.frame_filter.shows(*sent))
+                .map(|(_, line)| line)
+                .collect();
+            let skip = shown.len().saturating_sub(height);
+            let lines: Vec<Line> = shown
+                .into_iter()
+                .skip(skip)
+                .map(|line| Line::from(line.as_str()))
+                .collect();
+            Paragraph::new(lines)
+        } else {
+            //let text: &str = &self.ip;
+            let text = format!(
+                "Version: {}\nBoard: {}\n{}",
+                self.version, self.board, self.ip
+            );
+            Paragraph::new(Text::from(text))
+        };
         let paragraph_block = paragraph.block(left_block_down);
         frame.render_widget(paragraph_block, left_chunk_lower);
+
+        if self.show_statistics {
+            let lines: Vec<Line> = self
+                .statistics
+                .lines()
+                .into_iter()
+                .map(Line::from)
+                .collect();
+            let area = frame.area().centered(
+                Constraint::Length(40),
+                Constraint::Length(u16::try_from(lines.len()).unwrap_or(0) + 2),
+            );
+            // Floats above the panes without changing their layout
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(lines).block(Block::bordered().title("Statistics")),
+                area,
+            );
+        }
+
+        if let Some(selected) = self.resource_tree {
+            let rows = resource_tree(&self.resources);
+            let area = frame.area().centered(
+                Constraint::Percentage(80),
+                Constraint::Length(u16::try_from(rows.len() + 2).unwrap_or(u16::MAX)),
+            );
+            // Keeps the selected row in view
+            let height = usize::from(area.height.saturating_sub(2));
+            let skip = (selected + 1).saturating_sub(height);
+            let lines: Vec<Line> = rows
+                .iter()
+                .enumerate()
+                .skip(skip)
+                .map(|(index, row)| {
+                    let mut line = fmt_tree_row(row, &self.resources, &self.theme);
+                    if index == selected {
+                        line = line.patch_style(Style::new().reversed());
+                    }
+                    line
+                })
+                .collect();
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(lines).block(
+                    Block::bordered()
+                        .title("Resources")
+                        .title_bottom("Enter: use path, Esc: close"),
+                ),
+                area,
+            );
+        }
+
+        if !self.color {
+            strip_colors(frame.buffer_mut());
+        }
     }
 }
 
@@ -501,22 +2377,29 @@ fn reset_terminal() {
         std::io::stdout(),
         crossterm::terminal::LeaveAlternateScreen,
         crossterm::event::DisableMouseCapture,
+        crossterm::event::DisableBracketedPaste,
         crossterm::cursor::Show
     )
     .unwrap();
 }
 
 pub fn show(
-    write_port: Box<dyn SerialPort>,
+    cli: &Cli,
+    replay: Option<Replay>,
+    connection_channel: Receiver<ConnectionEvent>,
     diagnostic_channel: Receiver<String>,
     configuration_channel: Receiver<Vec<u8>>,
     packet_channel: Receiver<Vec<u8>>,
+    frame_channel: Receiver<FrameInfo>,
 ) {
     let mut app = App::new(
-        write_port,
+        cli,
+        replay,
+        connection_channel,
         diagnostic_channel,
         configuration_channel,
         packet_channel,
+        frame_channel,
     );
     //app.poll_ifconfig();
 
@@ -532,6 +2415,7 @@ pub fn show(
         stdout,
         crossterm::terminal::EnterAlternateScreen,
         crossterm::event::EnableMouseCapture,
+        crossterm::event::EnableBracketedPaste,
         crossterm::cursor::Hide
     )
     .unwrap();
@@ -539,17 +2423,29 @@ pub fn show(
 
     terminal.clear().unwrap();
 
-    main_loop(app, terminal);
+    main_loop(&mut app, terminal);
 
     reset_terminal();
+
+    if app.session_file.is_some() {
+        match app.save_session() {
+            Ok(file_name) => println!("Saved session to {file_name}"),
+            Err(err) => eprintln!("Failed to save session: {err}"),
+        }
+    }
 }
 
-fn main_loop<B>(mut app: App, mut terminal: Terminal<B>)
+fn main_loop<B>(app: &mut App, mut terminal: Terminal<B>)
 where
     B: Backend,
 {
     const INTERVAL: Duration = Duration::from_millis(50);
-    const DEBOUNCE: Duration = Duration::from_millis(20); // 50 FPS
+    // Only one message per channel is read per iteration, so while waiting for
+    // the next frame the channels are still checked at least this often
+    const POLL: Duration = Duration::from_millis(20);
+
+    // Redraws without changes are not allowed to exceed the frame rate either
+    let interval = INTERVAL.max(app.frame_time);
 
     terminal.draw(|frame| app.draw(frame)).unwrap();
 
@@ -557,10 +2453,14 @@ where
     let mut debounce: Option<Instant> = None;
 
     loop {
-        let timeout = debounce.map_or(INTERVAL, |start| DEBOUNCE.saturating_sub(start.elapsed()));
+        let timeout = debounce.map_or(interval, |start| {
+            app.frame_time.saturating_sub(start.elapsed()).min(POLL)
+        });
         if crossterm::event::poll(timeout).unwrap() {
             let refresh = match crossterm::event::read().unwrap() {
                 Event::Key(key) => app.on_key(key),
+                Event::Mouse(mouse) => app.on_mouse(mouse),
+                Event::Paste(text) => app.on_paste(&text),
                 Event::Resize(_, _) => Refresh::Update,
                 _ => Refresh::Skip,
             };
@@ -572,50 +2472,458 @@ where
                 }
             }
         }
-        match app.diagnostic_channel.try_recv() {
-            Ok(data) => {
-                app.diagnostic_messages.push_str(&data);
-                debounce.get_or_insert_with(Instant::now);
-            }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(mpsc::TryRecvError::Disconnected) => panic!(),
-        }
-        match app.configuration_channel.try_recv() {
-            Ok(data) => {
-                let response = Packet::from_bytes(&data).unwrap();
-                let token = response.get_token();
-                for request in &mut app.configuration_requests {
-                    if request.message.get_token() == token {
-                        request.response = Some(CoapResponse {
-                            message: response.clone(),
-                        });
-                    }
+        if app.receive() {
+            debounce.get_or_insert_with(Instant::now);
+        }
+        while let Some(entry) = app.replay.as_mut().and_then(Replay::next_due) {
+            app.on_replay_entry(entry);
+            debounce.get_or_insert_with(Instant::now);
+        }
+        let mut retransmissions = vec![];
+        for request in &mut app.configuration_requests {
+            if request.check_timeout(app.coap_timeout) {
+                // Unanswered NONs are not a sign of a bad link
+                if request.is_confirmable() {
+                    app.link_health.add_failure();
                 }
-                app.configuration_packets.push(response);
                 debounce.get_or_insert_with(Instant::now);
             }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(mpsc::TryRecvError::Disconnected) => panic!(),
+            retransmissions.extend(request.retransmit());
         }
-        match app.packet_channel.try_recv() {
-            Ok(_data) => {
-                debounce.get_or_insert_with(Instant::now);
-            }
-            Err(mpsc::TryRecvError::Empty) => {}
-            Err(mpsc::TryRecvError::Disconnected) => panic!(),
+        for packet in retransmissions {
+            app.write_packet(&packet);
+            debounce.get_or_insert_with(Instant::now);
+        }
+        if app.advance_scan() | app.advance_ping() {
+            debounce.get_or_insert_with(Instant::now);
         }
         if debounce.map_or_else(
-            || last_render.elapsed() > INTERVAL,
-            |debounce| debounce.elapsed() > DEBOUNCE,
+            || last_render.elapsed() > interval,
+            |debounce| debounce.elapsed() > app.frame_time,
         ) {
-            terminal.draw(|frame| app.draw(frame)).unwrap();
+            let completed = terminal.draw(|frame| app.draw(frame)).unwrap();
             last_render = Instant::now();
             debounce = None;
+            if app.screenshot_requested {
+                app.screenshot_requested = false;
+                app.status = match save_screen(completed.buffer) {
+                    Ok(file_name) => format!("Saved screen to {file_name}"),
+                    Err(err) => format!("Failed to save screen: {err}"),
+                };
+                // Show the outcome right away
+                debounce = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: String) -> Result<(), String> {
+    // On X11 the copied text is only available while the clipboard is alive
+    static CLIPBOARD: std::sync::Mutex<Option<arboard::Clipboard>> = std::sync::Mutex::new(None);
+    let mut clipboard = CLIPBOARD.lock().map_err(|err| err.to_string())?;
+    if clipboard.is_none() {
+        *clipboard = Some(arboard::Clipboard::new().map_err(|err| err.to_string())?);
+    }
+    clipboard
+        .as_mut()
+        .unwrap()
+        .set_text(text)
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: String) -> Result<(), String> {
+    Err("Jelly was built without the clipboard feature".to_string())
+}
+
+/// Keeps only the lines containing the filter, with the matches highlighted
+fn filtered_lines(text: &str, filter: &DiagnosticFilter) -> Vec<Line<'static>> {
+    let mut lines = vec![];
+    for line in text.lines() {
+        let matches = filter.find_matches(line);
+        if matches.is_empty() && !filter.needle.is_empty() {
+            continue;
+        }
+        let mut spans = vec![];
+        let mut last = 0;
+        for range in matches {
+            spans.push(Span::from(line[last..range.start].to_string()));
+            spans.push(Span::styled(
+                line[range.clone()].to_string(),
+                Style::new().reversed(),
+            ));
+            last = range.end;
+        }
+        spans.push(Span::from(line[last..].to_string()));
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
+/// Reverses the parts of a rendered line matching the search, keeping the
+/// styles of its spans. Matches across span boundaries are not found.
+fn highlight_matches(line: &Line<'static>, search: &DiagnosticFilter) -> Line<'static> {
+    let mut spans = vec![];
+    for span in &line.spans {
+        let content = span.content.as_ref();
+        let mut last = 0;
+        for range in search.find_matches(content) {
+            spans.push(Span::styled(
+                content[last..range.start].to_string(),
+                span.style,
+            ));
+            spans.push(Span::styled(
+                content[range.clone()].to_string(),
+                span.style.reversed(),
+            ));
+            last = range.end;
+        }
+        spans.push(Span::styled(content[last..].to_string(), span.style));
+    }
+    Line::from(spans).style(line.style)
+}
+
+/// Resets all colors but keeps modifiers, so highlights stay visible on monochrome terminals
+fn strip_colors(buffer: &mut Buffer) {
+    for cell in buffer.content.iter_mut() {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
+}
+
+/// One diagnostic output, request or response of the session export
+struct SessionEntry {
+    time: SystemTime,
+    kind: &'static str,
+    summary: String,
+    text: String,
+    /// Encoded CoAP packet, lets `--replay` reconstruct requests and responses
+    frame: Option<Vec<u8>>,
+}
+
+fn fmt_session_text(entries: &[SessionEntry], timestamps: &TimestampFormat) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        _ = writeln!(
+            out,
+            "[{}] {}: {}",
+            timestamps.format(entry.time),
+            entry.kind,
+            entry.summary
+        );
+        for line in entry.text.lines() {
+            _ = writeln!(out, "  {}", line.trim_start());
+        }
+    }
+    out
+}
+
+fn fmt_session_json(entries: &[SessionEntry], timestamps: &TimestampFormat) -> String {
+    let entries: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+            let unix_ms = entry
+                .time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            serde_json::json!({
+                "time": timestamps.format(entry.time),
+                "unix_ms": unix_ms,
+                "kind": entry.kind,
+                "summary": entry.summary,
+                "text": entry.text,
+                "frame": entry.frame.as_deref().map(fmt_hex),
+            })
+        })
+        .collect();
+    // Serializing plain JSON values can not fail
+    serde_json::to_string_pretty(&entries).unwrap_or_default()
+}
+
+/// Breaks `text` into lines of at most `width` columns, wide characters are never split
+fn wrap_to_width(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut line_width = 0;
+    for c in text.chars() {
+        let char_width = Span::raw(c.to_string()).width();
+        if line_width + char_width > width && !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        line.push(c);
+        line_width += char_width;
+    }
+    lines.push(line);
+    lines
+}
+
+/// Wraps every line of the input on its own, like the input box shows it
+fn wrap_input(text: &str, width: usize) -> Vec<String> {
+    text.split('\n')
+        .flat_map(|line| wrap_to_width(line, width))
+        .collect()
+}
+
+/// Like `request_path`, followed by the Uri-Query options as `?a=1&b=2`
+fn request_target(packet: &Packet) -> String {
+    let mut target = request_path(packet);
+    let query: Vec<String> = packet
+        .get_option(CoapOption::UriQuery)
+        .into_iter()
+        .flatten()
+        .map(|part| String::from_utf8_lossy(part).to_string())
+        .collect();
+    if !query.is_empty() {
+        _ = write!(target, "?{}", query.join("&"));
+    }
+    target
+}
+
+/// Uri-Path options joined with `/`, without a leading slash
+fn request_path(packet: &Packet) -> String {
+    let segments: Vec<String> = packet
+        .get_option(CoapOption::UriPath)
+        .into_iter()
+        .flatten()
+        .map(|segment| String::from_utf8_lossy(segment).to_string())
+        .collect();
+    segments.join("/")
+}
+
+/// The note and the number of lines it takes up in the CoAP log
+fn fmt_log_note(note: &LogNote, theme: &Theme) -> (Paragraph<'static>, usize) {
+    match note {
+        LogNote::Malformed(error) => (Paragraph::new(Line::styled(error.clone(), theme.error)), 1),
+        LogNote::Report { title, text } => {
+            let block = Block::new()
+                .borders(Borders::TOP | Borders::BOTTOM)
+                .border_style(Style::new().dim())
+                .title(format!("Jelly: {title}"))
+                .title_alignment(Alignment::Left);
+            let lines: Vec<Line> = text
+                .lines()
+                .map(|line| Line::from(line.to_string()))
+                .collect();
+            let height = lines.len() + 2;
+            (Paragraph::new(lines).block(block), height)
+        }
+    }
+}
+
+fn fmt_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// One line per option of the packet, with the value decoded for well known
+/// options and as hex otherwise
+fn fmt_options(packet: &Packet, arrow: &str) -> Vec<Line<'static>> {
+    let mut lines = vec![];
+    for (&number, values) in packet.options() {
+        let option = CoapOption::from(number);
+        for value in values {
+            lines.push(Line::styled(
+                format!(
+                    "  {arrow} {option:?} ({number}): {}",
+                    fmt_option_value(option, value)
+                ),
+                Style::new().dim(),
+            ));
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            format!("  {arrow} No options"),
+            Style::new().dim(),
+        ));
+    }
+    lines
+}
+
+fn fmt_option_value(option: CoapOption, value: &[u8]) -> String {
+    let uint = || {
+        value
+            .iter()
+            .fold(0u64, |number, &byte| (number << 8) | u64::from(byte))
+    };
+    match option {
+        CoapOption::UriHost
+        | CoapOption::UriPath
+        | CoapOption::UriQuery
+        | CoapOption::LocationPath
+        | CoapOption::LocationQuery
+        | CoapOption::ProxyUri
+        | CoapOption::ProxyScheme => format!("\"{}\"", String::from_utf8_lossy(value)),
+        CoapOption::ContentFormat | CoapOption::Accept => {
+            let number = uint();
+            match usize::try_from(number)
+                .ok()
+                .and_then(|number| ContentFormat::try_from(number).ok())
+            {
+                Some(cf) => format!("{cf:?} ({number})"),
+                None => number.to_string(),
+            }
+        }
+        CoapOption::Block1 | CoapOption::Block2 => match BlockValue::try_from(value.to_vec()) {
+            Ok(block) => format!(
+                "block {} of {} bytes{}",
+                block.num,
+                block.size(),
+                if block.more { ", more follow" } else { "" }
+            ),
+            Err(_) => format!("0x{}", fmt_hex(value)),
+        },
+        CoapOption::Observe
+        | CoapOption::UriPort
+        | CoapOption::MaxAge
+        | CoapOption::Size1
+        | CoapOption::Size2
+        | CoapOption::NoResponse => uint().to_string(),
+        _ if value.is_empty() => "empty".to_string(),
+        _ => format!("0x{}", fmt_hex(value)),
+    }
+}
+
+/// Offset, hex bytes and printable ASCII, 16 bytes per line
+fn fmt_hexdump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        char::from(byte)
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("  {:04x}  {:<47}  |{ascii}|", index * 16, hex.join(" "))
+        })
+        .collect()
+}
+
+/// Writes the visible screen as plain text, without any styling, into a new file
+fn save_screen(buffer: &Buffer) -> io::Result<String> {
+    let mut out = String::new();
+    for y in buffer.area.top()..buffer.area.bottom() {
+        let mut line = String::new();
+        let mut hidden = 0;
+        for x in buffer.area.left()..buffer.area.right() {
+            // Cells covered by a preceding wide character only contain filler
+            if hidden > 0 {
+                hidden -= 1;
+                continue;
+            }
+            let symbol = buffer[(x, y)].symbol();
+            hidden = Span::raw(symbol).width().saturating_sub(1);
+            line.push_str(symbol);
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let file_name = format!("jelly-screen-{timestamp}.txt");
+    fs::write(&file_name, out)?;
+    Ok(file_name)
+}
+
+/// Like `fmt_packet`, but with syntax highlighting for link-format payloads,
+/// or with the payload as a hexdump regardless of its content format
+fn fmt_packet_lines(
+    packet: &Packet,
+    compact: bool,
+    hexdump: bool,
+    theme: &Theme,
+) -> Vec<Line<'static>> {
+    // The hexdump must not depend on decoding a payload that may not match its format
+    if hexdump {
+        let mut lines = vec![Line::from(fmt_packet_header(packet))];
+        lines.extend(fmt_hexdump(&packet.payload).into_iter().map(Line::from));
+        return lines;
+    }
+    let text = fmt_packet(packet, compact);
+    if let (MessageClass::Response(_), Some(ContentFormat::ApplicationLinkFormat)) =
+        (packet.header.code, packet.get_content_format())
+    {
+        let header = text.lines().next().unwrap_or_default().to_string();
+        let mut lines = vec![Line::from(header)];
+        lines.extend(fmt_link_format(&packet.payload, theme));
+        return lines;
+    }
+    text.lines()
+        .map(|line| Line::from(line.to_string()))
+        .collect()
+}
+
+/// One line per link: resource paths, attribute keys and quoted values
+/// are styled differently to make long discovery responses easier to scan
+fn fmt_link_format(payload: &[u8], theme: &Theme) -> Vec<Line<'static>> {
+    parse_link_format(payload)
+        .into_iter()
+        .map(|link| {
+            let mut spans = vec![
+                Span::from("  "),
+                Span::styled(format!("<{}>", link.path), theme.path),
+            ];
+            for (key, value) in link.attributes {
+                spans.push(Span::from(";"));
+                let Some(value) = value else {
+                    spans.push(Span::styled(key, theme.attribute));
+                    continue;
+                };
+                spans.push(Span::styled(format!("{key}="), theme.attribute));
+                if value.starts_with('"') {
+                    spans.push(Span::styled(value.to_string(), Style::new().dim()));
+                } else {
+                    spans.push(Span::from(value.to_string()));
+                }
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Indented path segment, with the attributes of the link next to it
+fn fmt_tree_row(row: &TreeRow, links: &[LinkEntry], theme: &Theme) -> Line<'static> {
+    let indent = "  ".repeat(row.depth);
+    let Some(link) = row.link else {
+        return Line::from(format!("{indent}{}/", row.segment));
+    };
+    let mut spans = vec![Span::styled(format!("{indent}{}", row.segment), theme.path)];
+    for (key, value) in &links[link].attributes {
+        spans.push(Span::from(" "));
+        match value {
+            Some(value) => spans.push(Span::styled(format!("{key}={value}"), Style::new().dim())),
+            None => spans.push(Span::styled(key.clone(), Style::new().dim())),
         }
     }
+    Line::from(spans)
+}
+
+/// Formats a packet for display, `compact` keeps CBOR payloads on a single line
+fn fmt_packet(packet: &Packet, compact: bool) -> String {
+    let mut out = fmt_packet_header(packet);
+    if let MessageClass::Response(_) = packet.header.code {
+        let payload = match packet.get_content_format() {
+            Some(cf) => fmt_payload(packet, cf, compact),
+            None => "Empty Payload".to_string(),
+        };
+        _ = write!(out, "\n  {}", payload.replace('\n', "\n  "));
+    }
+    out
 }
 
-fn fmt_packet(packet: &Packet) -> String {
+/// First line of `fmt_packet`, without the payload
+fn fmt_packet_header(packet: &Packet) -> String {
     // When writing to a String `write!` will never fail.
     // Therefore the Result is ignored with `_ = write!()`.
     let mut out = String::new();
@@ -623,10 +2931,15 @@ fn fmt_packet(packet: &Packet) -> String {
         MessageClass::Empty => _ = write!(out, "Empty"),
         MessageClass::Request(rtype) => {
             _ = write!(out, " ← Req({rtype:?} ");
-            let option_list = packet.get_option(CoapOption::UriPath).unwrap();
-            for option in option_list {
+            let option_list = packet.get_option(CoapOption::UriPath);
+            for option in option_list.into_iter().flatten() {
                 _ = write!(out, "/{}", String::from_utf8_lossy(option));
             }
+            let query_list = packet.get_option(CoapOption::UriQuery);
+            for (index, option) in query_list.into_iter().flatten().enumerate() {
+                let separator = if index == 0 { '?' } else { '&' };
+                _ = write!(out, "{separator}{}", String::from_utf8_lossy(option));
+            }
             _ = write!(
                 out,
                 ")[0x{:04x}]",
@@ -636,26 +2949,16 @@ fn fmt_packet(packet: &Packet) -> String {
         MessageClass::Response(rtype) => {
             _ = write!(out, " → Res({rtype:?}");
             if let Some(cf) = packet.get_content_format() {
-                let payload = match cf {
-                    ContentFormat::ApplicationLinkFormat => {
-                        // change me back | ContentFormat::TextPlain
-                        String::from_utf8_lossy(&packet.payload).replace(',', "\n  ")
-                    }
-                    ContentFormat::TextPlain => {
-                        String::from_utf8_lossy(&packet.payload).to_string()
-                    }
-                    _ => todo!(),
-                };
                 _ = write!(
                     out,
-                    "/{cf:?})[0x{:04x}] {:} bytes\n  {payload}",
+                    "/{cf:?})[0x{:04x}] {:} bytes",
                     u16::from_le_bytes(packet.get_token().try_into().unwrap_or([0xff, 0xff])),
-                    payload.len()
+                    packet.payload.len()
                 );
             } else {
                 _ = write!(
                     out,
-                    ")[0x{:04x}]\n  Empty Payload",
+                    ")[0x{:04x}]",
                     u16::from_le_bytes(packet.get_token().try_into().unwrap_or([0xff, 0xff]))
                 );
             }
@@ -665,7 +2968,87 @@ fn fmt_packet(packet: &Packet) -> String {
     out
 }
 
-fn fmt_ps(packet: &Packet) -> String {
+/// Formats the payload according to its content format
+fn fmt_payload(packet: &Packet, cf: ContentFormat, compact: bool) -> String {
+    match cf {
+        ContentFormat::ApplicationLinkFormat => {
+            // change me back | ContentFormat::TextPlain
+            String::from_utf8_lossy(&packet.payload).replace(',', "\n")
+        }
+        ContentFormat::TextPlain => String::from_utf8_lossy(&packet.payload).to_string(),
+        ContentFormat::ApplicationCBOR => fmt_cbor(&packet.payload, compact),
+        // JSON and other text formats are readable as they are, anything else as a hexdump
+        _ => match std::str::from_utf8(&packet.payload) {
+            Ok(text) => text.to_string(),
+            Err(_) => fmt_hexdump(&packet.payload)
+                .iter()
+                .map(|line| line.trim_start())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        },
+    }
+}
+
+/// Renders CBOR as diagnostic notation, either on a single line
+/// or with arrays and maps spread over indented lines
+fn fmt_cbor(payload: &[u8], compact: bool) -> String {
+    match StandaloneItem::from_cbor(payload) {
+        Ok(item) if compact => item.serialize(),
+        Ok(item) => {
+            let mut out = String::new();
+            fmt_cbor_item(item.item(), 0, &mut out);
+            out
+        }
+        Err(err) => format!("Invalid CBOR: {err}"),
+    }
+}
+
+fn fmt_cbor_item(item: &Item, depth: usize, out: &mut String) {
+    const INDENT: &str = "  ";
+    if let Ok(items) = item.get_array_items() {
+        let items: Vec<&Item> = items.collect();
+        if items.is_empty() {
+            out.push_str("[]");
+            return;
+        }
+        out.push_str("[\n");
+        for (index, item) in items.iter().enumerate() {
+            out.push_str(&INDENT.repeat(depth + 1));
+            fmt_cbor_item(item, depth + 1, out);
+            out.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+        }
+        out.push_str(&INDENT.repeat(depth));
+        out.push(']');
+    } else if let Ok(entries) = item.get_map_items() {
+        let entries: Vec<(&Item, &Item)> = entries.collect();
+        if entries.is_empty() {
+            out.push_str("{}");
+            return;
+        }
+        out.push_str("{\n");
+        for (index, (key, value)) in entries.iter().enumerate() {
+            out.push_str(&INDENT.repeat(depth + 1));
+            fmt_cbor_item(key, depth + 1, out);
+            out.push_str(": ");
+            fmt_cbor_item(value, depth + 1, out);
+            out.push_str(if index + 1 < entries.len() {
+                ",\n"
+            } else {
+                "\n"
+            });
+        }
+        out.push_str(&INDENT.repeat(depth));
+        out.push('}');
+    } else if let (Ok(tag), Ok(tagged)) = (item.get_tag(), item.get_tagged()) {
+        out.push_str(&format!("{tag}("));
+        fmt_cbor_item(tagged.item(), depth, out);
+        out.push(')');
+    } else {
+        out.push_str(&item.serialize());
+    }
+}
+
+fn fmt_ps(packet: &Packet, sort: PsSort) -> String {
     // When writing to a String `write!` will never fail.
     // Therefore the Result is ignored with `_ = write!()`.
     let mut out = String::new();
@@ -676,52 +3059,11 @@ fn fmt_ps(packet: &Packet) -> String {
         }
         MessageClass::Response(_rtype) => {
             if let Some(cf) = packet.get_content_format() {
-                let _payload = match cf {
+                match cf {
                     ContentFormat::TextPlain => {
-                        //_ = write!(out, "Total payload size: {}\n", packet.payload.len());
-                        _ = write!(
-                            out,
-                            "{:<20}|{:<5}|{:<5}|{:<5}|{:<10}|{:<10}|\n",
-                            "name", "stack", "used", "free", "start", "SP"
-                        );
-                        let mut last_zero = 0;
-                        while let Some(mut next_zero) =
-                            packet.payload[last_zero..].iter().position(|&x| x == 0)
-                        {
-                            next_zero = last_zero + next_zero;
-                            let name =
-                                String::from_utf8_lossy(&packet.payload[last_zero..next_zero]);
-                            next_zero += 1;
-
-                            let stack_size = u32::from_le_bytes(
-                                packet.payload[next_zero..(next_zero + 4)]
-                                    .try_into()
-                                    .unwrap(),
-                            );
-                            next_zero += 4;
-                            let stack_size_used = u32::from_le_bytes(
-                                packet.payload[next_zero..(next_zero + 4)]
-                                    .try_into()
-                                    .unwrap(),
-                            );
-                            next_zero += 4;
-                            let stack_start = u32::from_le_bytes(
-                                packet.payload[next_zero..(next_zero + 4)]
-                                    .try_into()
-                                    .unwrap(),
-                            );
-                            next_zero += 4;
-                            let stack_pointer = stack_start + stack_size + 52 - stack_size_used;
-                            let stack_free = stack_size - stack_size_used;
-                            _ = write!(
-                                out,
-                                "{name:<20}|{stack_size:<5}|{stack_size_used:<5}|{stack_free:<5}|{stack_start:#010x}|{stack_pointer:#010x}|\n"
-                            );
-                            //next_zero += 4;
-                            last_zero = next_zero;
-                        }
+                        out.push_str(&fmt_ps_table(parse_ps(&packet.payload), sort));
                     }
-                    _ => todo!(),
+                    _ => _ = write!(out, "Unexpected content format {cf:?}"),
                 };
             } else {
                 _ = write!(
@@ -735,3 +3077,404 @@ fn fmt_ps(packet: &Packet) -> String {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use clap::Parser;
+    use ratatui::backend::TestBackend;
+    use ratatui::style::Modifier;
+    use ratatui::style::Stylize;
+
+    use super::*;
+
+    /// App as started with `args`, without a device
+    fn app(args: &[&str]) -> App {
+        let cli = Cli::parse_from(std::iter::once("jelly").chain(args.iter().copied()));
+        App::new(
+            &cli,
+            None,
+            mpsc::channel().1,
+            mpsc::channel().1,
+            mpsc::channel().1,
+            mpsc::channel().1,
+            mpsc::channel().1,
+        )
+    }
+
+    fn render(app: &mut App, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Text of every row of the buffer
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// GET of `path`, answered with `payload`
+    fn answered(path: &str, content_format: ContentFormat, payload: &[u8]) -> Request {
+        let mut coap: CoapRequest<String> = CoapRequest::new();
+        coap.set_method(Method::Get);
+        coap.set_path(path);
+        let mut request = Request::new(coap);
+        let mut response = Packet::new();
+        response.header.set_type(MessageType::Acknowledgement);
+        response.header.code = MessageClass::Response(ResponseType::Content);
+        response.set_content_format(content_format);
+        response.payload = payload.to_vec();
+        request.on_response(response);
+        request
+    }
+
+    /// A discovery response and a timed out request, both styled by the theme
+    fn colorful_app(args: &[&str]) -> App {
+        let mut app = app(args);
+        app.configuration_requests.push(answered(
+            "/.well-known/core",
+            ContentFormat::ApplicationLinkFormat,
+            b"</riot/board>;ct=0",
+        ));
+        let mut timed_out = answered("/riot/board", ContentFormat::TextPlain, b"");
+        timed_out.coap.response = None;
+        timed_out.timed_out = true;
+        app.configuration_requests.push(timed_out);
+        app
+    }
+
+    #[test]
+    fn reconnect_rediscovers_the_resources() {
+        let mut app = app(&[]);
+        app.on_connection_event(ConnectionEvent::Connected(Box::new(std::io::sink())));
+        app.on_connection_event(ConnectionEvent::Disconnected);
+        app.configuration_requests.clear();
+        app.on_connection_event(ConnectionEvent::Connected(Box::new(std::io::sink())));
+        let paths: Vec<String> = app
+            .configuration_requests
+            .iter()
+            .map(|req| req.coap.get_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "riot/ver",
+                "riot/board",
+                ".well-known/ifconfig",
+                ".well-known/core"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejected_settings_close_the_channels_without_a_panic() {
+        let cli = Cli::parse_from(["jelly"]);
+        let (connection, connection_channel) = mpsc::channel();
+        let mut app = App::new(
+            &cli,
+            None,
+            connection_channel,
+            mpsc::channel().1,
+            mpsc::channel().1,
+            mpsc::channel().1,
+            mpsc::channel().1,
+        );
+        connection
+            .send(ConnectionEvent::Rejected("Invalid baud rate".to_string()))
+            .unwrap();
+        drop(connection);
+        assert!(app.receive());
+        assert!(!app.receive());
+        let text = rows(&render(&mut app, 160, 24)).join("\n");
+        assert!(
+            text.contains("rejected the serial settings: Invalid baud rate"),
+            "{text}"
+        );
+    }
+
+    fn press(app: &mut App, code: KeyCode, modifiers: KeyModifiers) -> Refresh {
+        app.on_key(KeyEvent::new(code, modifiers))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    #[test]
+    fn right_at_the_end_of_input_completes_and_stays() {
+        let mut app = app(&[]);
+        type_text(&mut app, "/shell/ve");
+        for _ in 0..5 {
+            let refresh = press(&mut app, KeyCode::Right, KeyModifiers::NONE);
+            assert!(!matches!(refresh, Refresh::Quit));
+        }
+        assert_eq!(app.user_command, "/shell/version");
+        assert_eq!(app.input_position, app.user_command.len());
+    }
+
+    #[test]
+    fn arrows_stop_at_the_input_boundaries() {
+        let mut app = app(&[]);
+        for _ in 0..3 {
+            assert!(!matches!(
+                press(&mut app, KeyCode::Right, KeyModifiers::NONE),
+                Refresh::Quit
+            ));
+            assert!(!matches!(
+                press(&mut app, KeyCode::Left, KeyModifiers::NONE),
+                Refresh::Quit
+            ));
+        }
+        assert_eq!(app.input_position, 0);
+
+        type_text(&mut app, "ö");
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Left, KeyModifiers::NONE);
+        }
+        assert_eq!(app.input_position, 0);
+        assert_eq!(app.user_command, "ö");
+    }
+
+    fn ctrl(app: &mut App, c: char) {
+        press(app, KeyCode::Char(c), KeyModifiers::CONTROL);
+    }
+
+    #[test]
+    fn kill_and_yank_in_the_middle_of_the_input() {
+        let mut app = app(&[]);
+        type_text(&mut app, "PUT /config/interval 5000");
+        for _ in 0..5 {
+            press(&mut app, KeyCode::Left, KeyModifiers::NONE);
+        }
+
+        ctrl(&mut app, 'k');
+        assert_eq!(app.user_command, "PUT /config/interval");
+        assert_eq!(app.input_position, app.user_command.len());
+
+        ctrl(&mut app, 'a');
+        assert_eq!(app.input_position, 0);
+        ctrl(&mut app, 'y');
+        assert_eq!(app.user_command, " 5000PUT /config/interval");
+        assert_eq!(app.input_position, 5);
+
+        ctrl(&mut app, 'u');
+        assert_eq!(app.user_command, "PUT /config/interval");
+        assert_eq!(app.input_position, 0);
+
+        ctrl(&mut app, 'e');
+        assert_eq!(app.input_position, app.user_command.len());
+        ctrl(&mut app, 'y');
+        assert_eq!(app.user_command, "PUT /config/interval 5000");
+    }
+
+    #[test]
+    fn ctrl_w_fills_the_kill_ring() {
+        let mut app = app(&[]);
+        type_text(&mut app, "GET /riot/board");
+        ctrl(&mut app, 'w');
+        assert_eq!(app.user_command, "GET /riot/");
+        ctrl(&mut app, 'y');
+        assert_eq!(app.user_command, "GET /riot/board");
+    }
+
+    #[test]
+    fn yank_with_empty_kill_ring_changes_nothing() {
+        let mut app = app(&[]);
+        type_text(&mut app, "help");
+        press(&mut app, KeyCode::Left, KeyModifiers::NONE);
+        ctrl(&mut app, 'y');
+        assert_eq!(app.user_command, "help");
+        assert_eq!(app.input_position, 3);
+    }
+
+    #[test]
+    fn long_request_title_wraps_into_the_body() {
+        let path = "/sensors/building-a/floor-3/room-42/temperature/0";
+        let mut app = app(&[]);
+        app.configuration_requests
+            .push(answered(path, ContentFormat::TextPlain, b"21.5"));
+        let rows = rows(&render(&mut app, 80, 24));
+
+        let start = rows.iter().position(|row| row.contains("Req(Get")).unwrap();
+        let column = rows[start].chars().position(|c| c == '←').unwrap();
+        let wrapped: String = rows[start..]
+            .iter()
+            .take_while(|row| !row.contains("Res("))
+            .map(|row| {
+                let rest: String = row.chars().skip(column).collect();
+                rest.trim_end_matches([' ', '│', '║', '█']).to_string()
+            })
+            .collect();
+        assert!(wrapped.contains(path), "{wrapped}");
+        assert!(rows.iter().any(|row| row.contains("21.5")));
+    }
+
+    #[test]
+    fn ascii_status_glyphs() {
+        let mut app = app(&["--ascii"]);
+        let mut retried = answered("/riot/board", ContentFormat::TextPlain, b"");
+        retried.coap.response = None;
+        retried.retransmissions = 2;
+        app.configuration_requests.push(retried);
+        let text = rows(&render(&mut app, 160, 24)).join("\n");
+        assert!(text.contains("[--] not connected"), "{text}");
+        assert!(text.contains("retry 2"), "{text}");
+        assert!(!text.contains('🪼') && !text.contains('❌') && !text.contains('↻'));
+    }
+
+    /// Foreground color of the first cell of `needle`, which has to be on screen
+    fn fg_of(buffer: &Buffer, needle: &str) -> Color {
+        let rows = rows(buffer);
+        let (y, row) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.contains(needle))
+            .unwrap();
+        let x = row[..row.find(needle).unwrap()].chars().count();
+        buffer[(u16::try_from(x).unwrap(), u16::try_from(y).unwrap())].fg
+    }
+
+    #[test]
+    fn themes_color_paths_attributes_and_errors() {
+        for (theme, path, attribute, error) in [
+            ("dark", Color::Cyan, Color::Yellow, Color::Red),
+            ("light", Color::Blue, Color::Magenta, Color::Red),
+            (
+                "solarized",
+                Color::Rgb(0x26, 0x8b, 0xd2),
+                Color::Rgb(0xb5, 0x89, 0x00),
+                Color::Rgb(0xdc, 0x32, 0x2f),
+            ),
+        ] {
+            let args = ["--color", "always", "--theme", theme];
+            let buffer = render(&mut colorful_app(&args), 140, 30);
+            assert_eq!(fg_of(&buffer, "</riot/board>"), path, "{theme}");
+            assert_eq!(fg_of(&buffer, "ct="), attribute, "{theme}");
+            assert_eq!(fg_of(&buffer, "Timed out"), error, "{theme}");
+        }
+    }
+
+    #[test]
+    fn no_colors_with_color_never() {
+        assert!(!Cli::parse_from(["jelly", "--color", "never"]).use_color());
+        assert!(Cli::parse_from(["jelly", "--color", "always"]).use_color());
+
+        let mut terminal = Terminal::new(TestBackend::new(20, 1)).unwrap();
+        terminal
+            .draw(|frame| {
+                let line = Line::from(vec!["error".red().on_blue(), " ok".bold()]);
+                frame.render_widget(Paragraph::new(line), frame.area());
+                strip_colors(frame.buffer_mut());
+            })
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        assert!(buffer
+            .content
+            .iter()
+            .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+        // Highlights stay visible on monochrome terminals
+        assert!(buffer[(6, 0)].modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn clearing_the_coap_log_keeps_observations() {
+        let mut app = app(&[]);
+        let mut observed = answered("/riot/board", ContentFormat::TextPlain, b"");
+        observed.observing = true;
+        app.configuration_requests.push(observed);
+        app.configuration_requests
+            .push(answered("/riot/ver", ContentFormat::TextPlain, b""));
+        app.scroll_target = ScrollTarget::Configuration;
+        app.clear_target();
+        assert_eq!(app.configuration_requests.len(), 1);
+        let message_id = app.message_id;
+        assert!(app.cancel_observation("/riot/board"));
+        assert_eq!(app.message_id, message_id.wrapping_add(1));
+        assert!(!app.configuration_requests[0].observing);
+    }
+
+    /// App with one request that still awaits its response
+    fn busy_app(args: &[&str]) -> App {
+        let mut app = app(args);
+        let mut pending = answered("/riot/board", ContentFormat::TextPlain, b"");
+        pending.coap.response = None;
+        app.configuration_requests.push(pending);
+        app
+    }
+
+    #[test]
+    fn esc_asks_before_quitting_with_requests_in_flight() {
+        let mut app = busy_app(&[]);
+        assert!(matches!(
+            press(&mut app, KeyCode::Esc, KeyModifiers::NONE),
+            Refresh::Update
+        ));
+        assert_eq!(
+            app.status,
+            "1 requests in flight, press Esc or Ctrl+C again to quit"
+        );
+        assert!(matches!(
+            press(&mut app, KeyCode::Esc, KeyModifiers::NONE),
+            Refresh::Quit
+        ));
+    }
+
+    #[test]
+    fn ctrl_c_asks_before_quitting_with_requests_in_flight() {
+        let ctrl_c = |app: &mut App| press(app, KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let mut app = busy_app(&[]);
+        assert!(matches!(ctrl_c(&mut app), Refresh::Update));
+        assert!(matches!(ctrl_c(&mut app), Refresh::Quit));
+
+        let mut app = busy_app(&["--force-quit"]);
+        assert!(matches!(ctrl_c(&mut app), Refresh::Quit));
+    }
+
+    #[test]
+    fn scan_summary_is_a_jelly_entry_in_the_coap_log() {
+        let mut app = app(&[]);
+        app.scan = Some(Scan::new(vec![]));
+        let diagnostics = app.diagnostic_messages.len();
+        assert!(app.advance_scan());
+        assert_eq!(app.diagnostic_messages.len(), diagnostics);
+        let text = rows(&render(&mut app, 160, 24)).join("\n");
+        assert!(text.contains("Jelly: SCAN"), "{text}");
+        assert!(text.contains("SCAN of 0 resources"), "{text}");
+    }
+
+    #[test]
+    fn ping_summary_is_a_jelly_entry_in_the_coap_log() {
+        let mut app = app(&[]);
+        app.ping = Some(Ping::new("/riot/board".to_string(), 0));
+        let diagnostics = app.diagnostic_messages.len();
+        assert!(app.advance_ping());
+        assert_eq!(app.diagnostic_messages.len(), diagnostics);
+        let text = rows(&render(&mut app, 160, 24)).join("\n");
+        assert!(text.contains("Jelly: PING"), "{text}");
+        assert!(text.contains("PING /riot/board, 0 requests"), "{text}");
+    }
+
+    #[test]
+    fn session_uses_the_timestamp_format() {
+        let entries = [SessionEntry {
+            time: UNIX_EPOCH + Duration::from_secs(3723),
+            kind: "diagnostic",
+            summary: "help".to_string(),
+            text: "Command list\n".to_string(),
+            frame: None,
+        }];
+        let text = fmt_session_text(&entries, &TimestampFormat::new("%H:%M:%S", false));
+        assert_eq!(text, "[01:02:03] diagnostic: help\n  Command list\n");
+        let text = fmt_session_text(&entries, &TimestampFormat::new("%s", false));
+        assert!(text.starts_with("[3723] diagnostic"));
+    }
+}